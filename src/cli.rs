@@ -0,0 +1,226 @@
+use anyhow::Context;
+
+use crate::mygame::{adapters::GpuOptions, headless::Headless, Options};
+
+pub const USAGE: &str = "\
+Usage: scatter [OPTIONS]
+
+Window:
+  --size <WxH>              Window size, or image size with --headless [1280x720]
+  --fullscreen, --windowed  Override the fullscreen state saved by the last run
+  --present-mode <MODE>     fifo, fifo_relaxed, mailbox or immediate
+
+Scene:
+  --scene <FILE.toml>       Scene to load instead of the built-in one, reloaded
+                            whenever it changes
+  --atmosphere <PRESET>     Override the scene's atmosphere with default,
+                            sunset, hazy or clear
+  --camera <X,Y,Z[,YAW,PITCH]>
+                            Override the scene's start position, angles in degrees
+
+Headless rendering:
+  --headless                Render without a window
+  --frames <N>              Frames to render [1]
+  --output <FILE.png>       Image to write, a run of # is replaced by the frame
+                            number to keep every frame, e.g. frame-###.png
+
+Benchmarking:
+  --benchmark <N>           Time N frames after a warm up, print the frame time
+                            statistics and exit
+
+GPU:
+  --backend <LIST>          vulkan, dx12, metal, gl, all or software
+  --power <PREF>            high, low or none
+  --adapter <N|NAME>        Adapter from --list-adapters
+  --validation <on|off>     API validation layers
+  --list-adapters           Print the adapters of the selected backends and exit
+
+  -h, --help                Print this help and exit
+
+GPU options default to the SCATTER_BACKEND, SCATTER_POWER, SCATTER_ADAPTER and
+SCATTER_VALIDATION environment variables.";
+
+/// What to do, from the command line.
+pub enum Command {
+    Run(Options),
+    Headless(Options, Headless),
+    ListAdapters(GpuOptions),
+    Help,
+}
+
+pub fn parse(args: impl IntoIterator<Item = String>) -> anyhow::Result<Command> {
+    let mut settings = Vec::new();
+    let mut fullscreen = None;
+    let mut headless = false;
+    let mut frames = None;
+    let mut output = None;
+    let mut list_adapters = false;
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().with_context(|| format!("{arg} needs a value"));
+
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(Command::Help),
+            "--list-adapters" => list_adapters = true,
+            "--fullscreen" => fullscreen = Some(true),
+            "--windowed" => fullscreen = Some(false),
+            "--headless" => headless = true,
+            "--frames" => {
+                let value = value()?;
+                frames = Some(
+                    value
+                        .parse()
+                        .ok()
+                        .filter(|frames| *frames > 0)
+                        .with_context(|| {
+                            format!("Invalid --frames: expected a number of frames, got {value:?}")
+                        })?,
+                );
+            }
+            "--output" => {
+                let value = value()?;
+                anyhow::ensure!(
+                    value.to_lowercase().ends_with(".png"),
+                    "Invalid --output: only PNG images can be written, got {value:?}"
+                );
+                output = Some(value);
+            }
+            _ => match arg.strip_prefix("--") {
+                Some(key) if Options::KEYS.contains(&key) || GpuOptions::KEYS.contains(&key) => {
+                    let value = value()?;
+                    settings.push((key.to_string(), value));
+                }
+                _ => anyhow::bail!("Unknown argument {arg}"),
+            },
+        }
+    }
+
+    // The environment is read only now so a bad variable doesn't get in the
+    // way of --help
+    let mut options = Options::from_env()?;
+    options.fullscreen = fullscreen;
+    for (key, value) in settings {
+        options
+            .set(&key, &value)
+            .with_context(|| format!("Invalid --{key}"))?;
+    }
+
+    if list_adapters {
+        return Ok(Command::ListAdapters(options.gpu));
+    }
+
+    if !headless {
+        anyhow::ensure!(
+            frames.is_none() && output.is_none(),
+            "--frames and --output need --headless"
+        );
+        return Ok(Command::Run(options));
+    }
+
+    anyhow::ensure!(
+        output.is_some() || options.benchmark.is_some(),
+        "--headless needs --output or --benchmark, there's nothing to do otherwise"
+    );
+    anyhow::ensure!(
+        options.fullscreen.is_none() && options.present_mode.is_none(),
+        "--fullscreen, --windowed and --present-mode need a window"
+    );
+    Ok(Command::Headless(options, Headless { frames, output }))
+}
+
+#[cfg(test)]
+mod tests {
+    use winit::dpi::PhysicalSize;
+
+    use super::*;
+
+    fn run(args: &[&str]) -> anyhow::Result<Command> {
+        parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    fn error(args: &[&str]) -> String {
+        format!("{:#}", run(args).err().unwrap())
+    }
+
+    #[test]
+    fn parses_window_options() {
+        let Ok(Command::Run(options)) = run(&["--size", "640x480", "--windowed"]) else {
+            panic!("expected a windowed run");
+        };
+        assert_eq!(options.size, Some(PhysicalSize::new(640, 480)));
+        assert_eq!(options.fullscreen, Some(false));
+        assert!(options.camera.is_none());
+    }
+
+    #[test]
+    fn later_flags_win() {
+        let Ok(Command::Run(options)) = run(&["--fullscreen", "--windowed", "--fullscreen"]) else {
+            panic!("expected a windowed run");
+        };
+        assert_eq!(options.fullscreen, Some(true));
+    }
+
+    #[test]
+    fn help_stops_parsing() {
+        assert!(matches!(run(&["-h"]), Ok(Command::Help)));
+        assert!(matches!(run(&["--help", "--bogus"]), Ok(Command::Help)));
+    }
+
+    #[test]
+    fn rejects_unknown_arguments() {
+        assert_eq!(error(&["--bogus"]), "Unknown argument --bogus");
+        assert_eq!(error(&["scene.toml"]), "Unknown argument scene.toml");
+    }
+
+    #[test]
+    fn rejects_missing_values() {
+        assert_eq!(error(&["--size"]), "--size needs a value");
+        assert_eq!(error(&["--headless", "--output"]), "--output needs a value");
+    }
+
+    #[test]
+    fn rejects_invalid_values() {
+        assert!(error(&["--size", "big"]).starts_with("Invalid --size: "));
+        assert!(error(&["--camera", "1,2"]).starts_with("Invalid --camera: "));
+        assert!(error(&["--headless", "--frames", "0"]).starts_with("Invalid --frames: "));
+        assert!(error(&["--headless", "--output", "a.jpg"]).starts_with("Invalid --output: "));
+    }
+
+    #[test]
+    fn dispatches_headless() {
+        let Ok(Command::Headless(_, headless)) =
+            run(&["--headless", "--frames", "3", "--output", "frame-#.png"])
+        else {
+            panic!("expected a headless run");
+        };
+        assert_eq!(headless.frames, Some(3));
+        assert_eq!(headless.output.as_deref(), Some("frame-#.png"));
+
+        assert!(matches!(
+            run(&["--headless", "--benchmark", "10"]),
+            Ok(Command::Headless(..))
+        ));
+        assert!(error(&["--headless"]).starts_with("--headless needs --output or --benchmark"));
+        assert_eq!(
+            error(&["--headless", "--output", "a.png", "--windowed"]),
+            "--fullscreen, --windowed and --present-mode need a window"
+        );
+        assert_eq!(
+            error(&["--output", "a.png"]),
+            "--frames and --output need --headless"
+        );
+    }
+
+    #[test]
+    fn dispatches_list_adapters() {
+        assert!(matches!(
+            run(&["--list-adapters", "--backend", "gl"]),
+            Ok(Command::ListAdapters(gpu)) if gpu.backends == wgpu::Backends::GL
+        ));
+        assert!(matches!(
+            run(&["--headless", "--list-adapters"]),
+            Ok(Command::ListAdapters(_))
+        ));
+    }
+}
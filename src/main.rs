@@ -1,15 +1,59 @@
+use std::process::ExitCode;
+
+use cli::Command;
 use mygame::MyGame;
 use window::GameWindow;
 use winit::event_loop::EventLoop;
 
+mod cli;
 mod mygame;
 mod window;
 
-fn main() {
+fn main() -> ExitCode {
     pretty_env_logger::init();
 
-    let event_loop = EventLoop::new().unwrap();
-    let mut window: GameWindow<MyGame<'_>> = GameWindow::new();
+    let options = match cli::parse(std::env::args().skip(1)) {
+        Ok(Command::Run(options)) => options,
+        Ok(Command::Headless(options, headless)) => {
+            return match mygame::headless::run(&options, &headless) {
+                Ok(()) => ExitCode::SUCCESS,
+                Err(e) => {
+                    log::error!("Headless rendering failed: {e:?}");
+                    ExitCode::FAILURE
+                }
+            };
+        }
+        Ok(Command::ListAdapters(options)) => {
+            mygame::adapters::print_list(&options);
+            return ExitCode::SUCCESS;
+        }
+        Ok(Command::Help) => {
+            println!("{}", cli::USAGE);
+            return ExitCode::SUCCESS;
+        }
+        Err(e) => {
+            eprintln!("{e:#}\nRun with --help for the available options.");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let event_loop = match EventLoop::new() {
+        Ok(event_loop) => event_loop,
+        Err(e) => {
+            log::error!("Failed to create the event loop: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let mut window: GameWindow<MyGame<'_>> = GameWindow::new(options);
+
+    if let Err(e) = event_loop.run_app(&mut window) {
+        log::error!("Event loop error: {e}");
+        return ExitCode::FAILURE;
+    }
 
-    event_loop.run_app(&mut window).expect("Application error");
+    if window.failed() {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
 }
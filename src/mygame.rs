@@ -1,18 +1,80 @@
+mod accumulation;
+pub mod adapters;
+mod atmosphere;
+mod benchmark;
+mod bloom;
 mod camera;
+mod config;
+mod debug_view;
+mod exposure;
+mod frame_limiter;
+pub mod headless;
+mod layout;
+mod lifecycle;
 mod mesh;
+mod obj;
+mod pipeline;
+mod pipeline_cache;
+mod png;
+mod postprocess;
+mod preprocessor;
+mod present;
+mod reflection;
+mod render_graph;
+mod scene;
+mod scene_graph;
+mod shader;
 mod texture;
-
-use std::{collections::HashMap, sync::Arc};
-
+mod tonemap;
+mod uniform;
+
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+use accumulation::{Accumulator, Sample};
+use adapters::GpuOptions;
+use anyhow::Context;
+use atmosphere::{Atmosphere, AtmosphereUniform};
+use benchmark::Benchmark;
+use bloom::{Bloom, BloomUniform};
 use bytemuck::{Pod, Zeroable};
-use camera::{Camera, CameraController};
+use camera::{Camera, CameraController, CameraPose, CameraUniform};
+use config::Config;
+use debug_view::{DebugSource, DebugView};
+use exposure::{
+    AutoExposure, AutoExposureUniform, ExposureReadback, ExposureState, HISTOGRAM_BINS,
+    HISTOGRAM_WORKGROUP_SIZE,
+};
+use frame_limiter::FrameLimiter;
+use layout::shader_layout;
+use lifecycle::SurfaceLifecycle;
 use mesh::{Mesh, Vertex};
+use pipeline::{BindGroupLayouts, Binding, Blend, PipelineFactory};
+use pipeline_cache::PipelineCache;
 use pollster::FutureExt;
+use postprocess::{PostPassDescriptor, PostProcessChain};
+use render_graph::{RenderGraph, TransientDesc, TransientPool};
+use scene::{MeshSource, Scene, SceneWatcher, MAX_MESH_NODES};
+use scene_graph::{ModelUniform, NodeId, SceneGraph};
+use shader::ShaderLibrary;
 use texture::Texture;
+use tonemap::{ToneMapping, ToneMappingUniform};
+use uniform::{UniformBuffer, UniformRing};
 use wgpu::util::DeviceExt;
-use winit::{dpi::PhysicalSize, event::WindowEvent, keyboard::KeyCode, window::Window};
+use winit::{
+    dpi::{PhysicalPosition, PhysicalSize},
+    event::WindowEvent,
+    keyboard::KeyCode,
+    window::Window,
+};
 
-use crate::window::Game;
+use crate::window::{Game, WindowConfig, FIXED_TIMESTEP};
 
 #[repr(C)]
 #[derive(Clone, Copy, Debug, Pod, Zeroable)]
@@ -20,295 +82,774 @@ pub struct GameInfo {
     resolution: [u32; 2],
     time: f32,
     delta_time: f32,
+    jitter: [f32; 2],
+    sample_index: u32,
+    _padding: u32,
+}
+
+shader_layout!(
+    GameInfo,
+    "GameInfo",
+    resolution,
+    time,
+    delta_time,
+    jitter,
+    sample_index
+);
+
+/// Format of the HDR scene target, which is also where samples are accumulated.
+const HDR_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+const MAX_ACCUMULATED_SAMPLES: u32 = 1024;
+
+/// Format rendered to without a window, read back as 8-bit sRGB.
+const HEADLESS_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+
+/// Settings MyGame is started with, from the command line.
+#[derive(Clone, Debug, Default)]
+pub struct Options {
+    pub gpu: GpuOptions,
+    /// Window size, or the size of the images rendered without a window.
+    pub size: Option<PhysicalSize<u32>>,
+    pub fullscreen: Option<bool>,
+    pub present_mode: Option<wgpu::PresentMode>,
+    /// Scene file to load instead of the built-in scene.
+    pub scene: Option<PathBuf>,
+    /// Overrides of the scene's atmosphere and camera, applied once at startup.
+    pub atmosphere: Option<Atmosphere>,
+    pub camera: Option<CameraPose>,
+    /// Number of frames to time before printing statistics and exiting.
+    pub benchmark: Option<u32>,
+    /// Settings saved by the last run, used where the command line sets none.
+    pub config: Config,
+}
+
+impl Options {
+    /// Options from the environment and the saved settings, before the command
+    /// line overrides them.
+    pub fn from_env() -> anyhow::Result<Self> {
+        Ok(Self {
+            gpu: GpuOptions::from_env()?,
+            config: Config::load(),
+            ..Default::default()
+        })
+    }
+
+    /// Names of the settings taking a value, besides those of [`GpuOptions`].
+    pub const KEYS: [&'static str; 6] = [
+        "size",
+        "present-mode",
+        "scene",
+        "atmosphere",
+        "camera",
+        "benchmark",
+    ];
+
+    /// Changes a setting from its textual value, see `--help` for the formats.
+    pub fn set(&mut self, key: &str, value: &str) -> anyhow::Result<()> {
+        match key {
+            "size" => {
+                self.size = Some(
+                    parse_size(value)
+                        .with_context(|| format!("expected a size like 1280x720, got {value:?}"))?,
+                )
+            }
+            "present-mode" => {
+                self.present_mode = Some(
+                    present::parse(value)
+                        .with_context(|| format!("unknown present mode {value:?}"))?,
+                )
+            }
+            "scene" => self.scene = Some(PathBuf::from(value)),
+            "atmosphere" => {
+                self.atmosphere = Some(Atmosphere::preset(value).with_context(|| {
+                    format!(
+                        "unknown atmosphere preset {value:?}, expected one of {}",
+                        Atmosphere::PRESETS.join(", ")
+                    )
+                })?)
+            }
+            "camera" => {
+                self.camera = Some(CameraPose::parse(value).with_context(|| {
+                    format!(
+                        "expected a camera pose like 0,0,-30 or 0,0,-30,0,10 with the pitch \
+                         strictly between -90 and 90, got {value:?}"
+                    )
+                })?)
+            }
+            "benchmark" => {
+                self.benchmark = Some(
+                    value
+                        .parse()
+                        .ok()
+                        .filter(|frames| *frames > 0)
+                        .with_context(|| format!("expected a number of frames, got {value:?}"))?,
+                )
+            }
+            key => self.gpu.set(key, value)?,
+        }
+        Ok(())
+    }
+}
+
+/// Parses `WxH` with both dimensions non-zero.
+fn parse_size(value: &str) -> Option<PhysicalSize<u32>> {
+    let (width, height) = value.split_once('x')?;
+    let size = PhysicalSize::new(width.trim().parse().ok()?, height.trim().parse().ok()?);
+    (size.width > 0 && size.height > 0).then_some(size)
+}
+
+/// Uniforms shared by the scene and exposure passes.
+struct Uniforms {
+    game_info: UniformBuffer<GameInfo>,
+    camera: UniformBuffer<CameraUniform>,
+    atmosphere: UniformBuffer<AtmosphereUniform>,
+    auto_exposure: UniformBuffer<AutoExposureUniform>,
+    /// World matrices of the nodes drawn this frame.
+    models: UniformRing<ModelUniform>,
+}
+
+/// GPU resources of a scene by name, placed by the nodes of its graph.
+struct SceneResources {
+    meshes: HashMap<String, Mesh>,
+    /// Bind groups of the materials with a texture, which they keep alive.
+    materials: HashMap<String, wgpu::BindGroup>,
 }
 
 #[allow(dead_code)]
 pub struct MyGame<'s> {
-    window: Arc<Window>,
-    surface: wgpu::Surface<'s>,
+    /// `None` when rendering headless.
+    window: Option<Arc<Window>>,
+    /// Dropped while the app is suspended.
+    surface: SurfaceLifecycle<wgpu::Surface<'s>>,
     surface_config: wgpu::SurfaceConfiguration,
+    present_modes: Vec<wgpu::PresentMode>,
+    options: Options,
+    instance: wgpu::Instance,
     adapter: wgpu::Adapter,
     device: wgpu::Device,
     queue: wgpu::Queue,
+    /// Set from the device lost callback, the device is recreated before the next frame.
+    device_lost: Arc<AtomicBool>,
 
+    bind_group_layouts: BindGroupLayouts,
     bind_groups: HashMap<String, wgpu::BindGroup>,
-    uniform_buffers: Vec<wgpu::Buffer>,
-
-    start_time: std::time::Instant,
-    prev_time: f32,
-
-    depth_texture: Texture,
-    pipelines: Vec<wgpu::RenderPipeline>,
-    meshes: Vec<Mesh>,
+    uniforms: Uniforms,
+    storage_buffers: Vec<wgpu::Buffer>,
 
+    /// Simulated time in seconds, advanced by each update.
+    time: f32,
+    /// Interpolated time of the last rendered frame.
+    frame_time: f32,
+    /// Set while the window is minimised or occluded, rendering and simulation
+    /// stop until it's visible again.
+    paused: bool,
+    occluded: bool,
+
+    transient_pool: TransientPool,
+    hdr_texture: Texture,
+    accumulator: Accumulator,
+    tone_mapping: ToneMapping,
+    auto_exposure: AutoExposure,
+    exposure_readback: ExposureReadback,
+    exposure_state: ExposureState,
+    bloom: Bloom,
+    post_chain: PostProcessChain,
+    shaders: ShaderLibrary,
+    pipeline_cache: PipelineCache,
+    pipelines: HashMap<String, wgpu::RenderPipeline>,
+    compute_pipelines: HashMap<String, wgpu::ComputePipeline>,
+    /// Quad the atmosphere is drawn with, behind the scene.
+    screen_quad: Mesh,
+    scene_resources: SceneResources,
+    scene_graph: SceneGraph,
+    scene: Scene,
+    /// Reloads the scene when its file changes, if one was given.
+    scene_watcher: Option<SceneWatcher>,
+
+    /// Camera before the last update, frames are rendered between the two.
+    previous_camera: Camera,
     camera: Camera,
     camera_controller: CameraController,
+    atmosphere: Atmosphere,
+    benchmark: Option<Benchmark>,
+
+    /// Secondary window showing intermediate targets, toggled with F3.
+    debug_view: Option<DebugView<'s>>,
+
+    config: Config,
+    frame_limiter: FrameLimiter,
 }
 
 impl MyGame<'_> {
-    pub async fn new(window: Arc<Window>) -> Self {
+    pub async fn new(window: Arc<Window>, options: &Options) -> anyhow::Result<Self> {
         let size = window.inner_size();
+        let scene = Scene::load(options.scene.as_deref())?;
+        let game = Self::create(Some(window), size, options, scene).await?;
+        game.configure_surface();
+        Ok(game)
+    }
 
-        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
-            backends: wgpu::Backends::VULKAN | wgpu::Backends::DX12,
-            flags: wgpu::InstanceFlags::VALIDATION,
-            ..Default::default()
-        });
-
-        let surface = instance
-            .create_surface(window.clone())
-            .expect("Failed to create surface");
+    /// Creates the device and resources without configuring the surface, which
+    /// is left to the caller as only one swapchain may exist per window.
+    /// Without a window everything is created for rendering `size` images.
+    async fn create(
+        window: Option<Arc<Window>>,
+        size: PhysicalSize<u32>,
+        options: &Options,
+        scene: Scene,
+    ) -> anyhow::Result<Self> {
+        let instance = options.gpu.instance();
+
+        let surface = window
+            .clone()
+            .map(|window| instance.create_surface(window))
+            .transpose()
+            .context("Failed to create the surface")?;
+
+        // Listing the adapters helps with choosing another one
+        let report_adapters = |_: &anyhow::Error| {
+            log::error!("Available adapters:\n{}", adapters::report(&options.gpu));
+        };
 
-        let adapter = instance
-            .request_adapter(&wgpu::RequestAdapterOptions {
-                power_preference: wgpu::PowerPreference::HighPerformance,
-                force_fallback_adapter: false,
-                compatible_surface: Some(&surface),
-            })
+        let adapter = options
+            .gpu
+            .adapter(&instance, surface.as_ref())
             .await
-            .expect("Failed to create an adapter");
+            .inspect_err(report_adapters)?;
 
         let (device, queue) = adapter
             .request_device(
                 &wgpu::DeviceDescriptor {
                     label: Some("device"),
-                    required_features: wgpu::Features::empty(),
+                    required_features: adapter.features() & wgpu::Features::PIPELINE_CACHE,
                     required_limits: wgpu::Limits::default(),
                     memory_hints: wgpu::MemoryHints::Performance,
                 },
                 None,
             )
             .block_on()
-            .expect("Failed to create device");
+            .context("Failed to create the device")
+            .inspect_err(report_adapters)?;
+
+        let device_lost = Arc::new(AtomicBool::new(false));
+        device.set_device_lost_callback({
+            let device_lost = device_lost.clone();
+            move |reason, message| {
+                // Dropping the device on recovery or exit also reports it lost
+                if reason == wgpu::DeviceLostReason::Unknown {
+                    log::error!("Device lost: {message}");
+                    device_lost.store(true, Ordering::Release);
+                }
+            }
+        });
 
         let device_info = adapter.get_info();
 
-        log::info!(
-            "Chosen device {} ({:?}) with driver {}.",
-            device_info.name,
-            device_info.device_type,
-            device_info.driver
-        );
+        log::info!("Chosen device {}.", adapters::describe(&device_info));
 
-        let surface_caps = surface.get_capabilities(&adapter);
+        let config = options.config.clone();
+        let surface_caps = match &surface {
+            Some(surface) => surface.get_capabilities(&adapter),
+            None => wgpu::SurfaceCapabilities {
+                formats: vec![HEADLESS_FORMAT],
+                present_modes: vec![wgpu::PresentMode::Fifo],
+                ..Default::default()
+            },
+        };
+        let present_mode = options
+            .present_mode
+            .or_else(|| config.parse("present_mode", present::parse))
+            .unwrap_or(wgpu::PresentMode::Fifo);
+        // Benchmarks run as fast as the present mode allows
+        let max_fps = config
+            .parse("max_fps", |v| v.parse().ok())
+            .filter(|_| options.benchmark.is_none());
+        let frame_limiter = FrameLimiter::new(max_fps);
 
-        const PREFERRED_PRESENT_MODE: wgpu::PresentMode = wgpu::PresentMode::Fifo;
         let surface_config = wgpu::SurfaceConfiguration {
             usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            // Prefer sRGB, tone mapping encodes manually otherwise
             format: surface_caps
                 .formats
-                .into_iter()
-                .filter(|s| s.is_srgb())
-                .next()
-                .expect("sRGB format must be available"),
+                .iter()
+                .copied()
+                .find(|s| s.is_srgb())
+                .or(surface_caps.formats.first().copied())
+                .context("The surface supports no formats on this adapter")?,
             width: size.width,
             height: size.height,
-            present_mode: surface_caps
-                .present_modes
-                .into_iter()
-                .find(|p| *p == PREFERRED_PRESENT_MODE)
-                .unwrap_or(wgpu::PresentMode::Fifo),
+            present_mode: present::supported(present_mode, &surface_caps.present_modes),
             desired_maximum_frame_latency: 2,
             alpha_mode: wgpu::CompositeAlphaMode::Opaque,
             view_formats: vec![],
         };
-        surface.configure(&device, &surface_config);
-
-        let camera = Camera::new();
-        let camera_controller = CameraController::new(5.0, 0.003);
 
-        let uniform_buffers = Self::create_uniform_buffers(&device, &camera, size);
-
-        let (bind_group_layouts, bind_groups) = Self::create_bind_groups(&device, &uniform_buffers);
-
-        let pipelines = Self::create_pipelines(&device, &surface_config, &bind_group_layouts);
-        let meshes = Self::create_meshes(&device);
-
-        let depth_texture =
-            Texture::create_depth_texture(&device, &surface_config, Some("depth_texture"));
+        let mut camera = Camera::new();
+        let mut camera_controller = CameraController::new(5.0, 0.003);
+        camera_controller.set_pose(
+            &mut camera,
+            options.camera.as_ref().unwrap_or(&scene.camera),
+        );
+        let atmosphere = options
+            .atmosphere
+            .clone()
+            .unwrap_or_else(|| scene.atmosphere.clone());
+        let tone_mapping = ToneMapping::new();
+        let auto_exposure = AutoExposure::new();
+
+        let uniforms = Self::create_uniforms(&device, &camera, &atmosphere, &auto_exposure, size);
+        let storage_buffers = Self::create_storage_buffers(&device);
+
+        let hdr_texture = Texture::create_render_target(
+            &device,
+            &surface_config,
+            HDR_FORMAT,
+            Some("hdr_texture"),
+        );
 
-        window.set_cursor_visible(false);
+        let shaders = ShaderLibrary::new();
+        if cfg!(debug_assertions) {
+            Self::verify_uniform_layouts(&shaders).context("Uniforms don't match the shaders")?;
+        }
+        let pipeline_cache = PipelineCache::load(&device, &device_info, &shaders);
+
+        let bloom = Bloom::new(
+            &device,
+            &surface_config,
+            &hdr_texture,
+            HDR_FORMAT,
+            &shaders.load(&device, "bloom.wgsl")?,
+            pipeline_cache.get(),
+        )
+        .context("Failed to create the bloom passes")?;
+
+        let white = Texture::from_image(
+            &device,
+            &queue,
+            &png::Image {
+                width: 1,
+                height: 1,
+                rgba: vec![255; 4],
+            },
+            Some("white_texture"),
+        );
+        let (bind_group_layouts, bind_groups) = Self::create_bind_groups(
+            &device,
+            &shaders,
+            &uniforms,
+            &storage_buffers,
+            &hdr_texture,
+            &white,
+        )
+        .context("Shader bindings don't match the resources")?;
+
+        let pipeline_factory = PipelineFactory {
+            device: &device,
+            layouts: &bind_group_layouts,
+            surface_format: surface_config.format,
+            cache: pipeline_cache.get(),
+        };
 
-        window
-            .set_cursor_grab(winit::window::CursorGrabMode::Locked)
-            .unwrap_or_else(|_| {
-                _ = window.set_cursor_grab(winit::window::CursorGrabMode::Confined)
-            });
+        let post_chain = Self::create_post_chain(
+            &pipeline_factory,
+            &surface_config,
+            &hdr_texture,
+            &tone_mapping,
+            &storage_buffers[1],
+            &bloom,
+            &shaders,
+        )
+        .context("Failed to create the post processing chain")?;
+        let pipelines = Self::create_pipelines(&pipeline_factory, &shaders)
+            .context("Failed to create the pipelines")?;
+        let compute_pipelines = Self::create_compute_pipelines(&pipeline_factory, &shaders)
+            .context("Failed to create the compute pipelines")?;
+        let exposure_readback = ExposureReadback::new(&device);
+        let (vertices, indices) = Self::quad();
+        let screen_quad = Mesh::create(&device, &vertices, &indices);
+        let scene_resources =
+            Self::create_scene_resources(&device, &queue, &bind_group_layouts, &scene)
+                .context("Failed to load the scene")?;
+        let scene_graph = SceneGraph::from_scene(&scene);
+        let scene_watcher = options.scene.as_deref().and_then(SceneWatcher::new);
+
+        if let Some(window) = &window {
+            window.set_cursor_visible(false);
+
+            window
+                .set_cursor_grab(winit::window::CursorGrabMode::Locked)
+                .unwrap_or_else(|_| {
+                    _ = window.set_cursor_grab(winit::window::CursorGrabMode::Confined)
+                });
+        }
 
-        Self {
+        Ok(Self {
             window,
-            surface,
+            surface: SurfaceLifecycle::new(surface),
             surface_config,
+            present_modes: surface_caps.present_modes,
+            options: options.clone(),
+            instance,
             adapter,
             device,
             queue,
+            device_lost,
 
+            bind_group_layouts,
             bind_groups,
-            uniform_buffers,
-
-            start_time: std::time::Instant::now(),
-            prev_time: 0.0,
-
-            depth_texture,
+            uniforms,
+            storage_buffers,
+
+            time: 0.0,
+            frame_time: 0.0,
+            paused: false,
+            occluded: false,
+
+            transient_pool: TransientPool::default(),
+            hdr_texture,
+            accumulator: Accumulator::new(MAX_ACCUMULATED_SAMPLES),
+            tone_mapping,
+            auto_exposure,
+            exposure_readback,
+            exposure_state: ExposureState::default(),
+            bloom,
+            post_chain,
+            shaders,
+            pipeline_cache,
             pipelines,
-            meshes,
-
+            compute_pipelines,
+            screen_quad,
+            scene_resources,
+            scene_graph,
+            scene,
+            scene_watcher,
+
+            previous_camera: camera.clone(),
             camera,
             camera_controller,
-        }
+            atmosphere,
+            benchmark: options.benchmark.map(Benchmark::new),
+
+            debug_view: None,
+
+            config,
+            frame_limiter,
+        })
     }
 
-    fn create_uniform_buffers(
+    fn create_uniforms(
         device: &wgpu::Device,
         camera: &Camera,
+        atmosphere: &Atmosphere,
+        auto_exposure: &AutoExposure,
         size: PhysicalSize<u32>,
-    ) -> Vec<wgpu::Buffer> {
-        let game_info = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("game_info"),
-            contents: bytemuck::cast_slice(&[GameInfo {
-                resolution: [size.width, size.height],
-                time: 0.0,
-                delta_time: 0.01,
-            }]),
-            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+    ) -> Uniforms {
+        Uniforms {
+            game_info: UniformBuffer::new(
+                device,
+                "game_info",
+                GameInfo {
+                    resolution: [size.width, size.height],
+                    time: 0.0,
+                    delta_time: 0.01,
+                    jitter: [0.0, 0.0],
+                    sample_index: 0,
+                    _padding: 0,
+                },
+            ),
+            camera: UniformBuffer::new(
+                device,
+                "camera",
+                camera.uniform(size.width as f32 / size.height as f32),
+            ),
+            atmosphere: UniformBuffer::new(device, "atmosphere", atmosphere.uniform()),
+            auto_exposure: UniformBuffer::new(device, "auto_exposure", auto_exposure.uniform()),
+            models: UniformRing::new(device, "models", MAX_MESH_NODES as u32),
+        }
+    }
+
+    fn create_storage_buffers(device: &wgpu::Device) -> Vec<wgpu::Buffer> {
+        let histogram = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("luminance_histogram"),
+            size: HISTOGRAM_BINS * std::mem::size_of::<u32>() as u64,
+            usage: wgpu::BufferUsages::STORAGE,
+            mapped_at_creation: false,
         });
 
-        let camera = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("camera"),
-            contents: bytemuck::cast_slice(&[camera.uniform()]),
-            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        let exposure = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("exposure_state"),
+            contents: bytemuck::cast_slice(&[ExposureState::default()]),
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
         });
 
-        return vec![game_info, camera];
+        vec![histogram, exposure]
     }
 
-    fn update_uniform_buffers(&mut self) {
-        // Update gameinfo buffer
-        let time = (std::time::Instant::now() - self.start_time).as_secs_f32();
-        let delta_time = time - self.prev_time;
-        self.prev_time = time;
+    /// Uploads the uniforms of a frame `alpha` steps past the last update,
+    /// returning the sample to accumulate.
+    fn update_uniform_buffers(&mut self, alpha: f32) -> Option<Sample> {
+        let camera = self.previous_camera.interpolate(&self.camera, alpha);
+        let aspect = self.surface_config.width as f32 / self.surface_config.height as f32;
+        self.uniforms.camera.set(camera.uniform(aspect));
+        self.uniforms.atmosphere.set(self.atmosphere.uniform());
+        // Interpolation keeps moving the camera for a step after the input stops
+        let camera_moved = self.uniforms.camera.flush(&self.queue);
+        if self.uniforms.atmosphere.flush(&self.queue) || camera_moved {
+            self.accumulator.reset();
+        }
+
+        let sample = self.accumulator.next_sample();
+        let jittered = sample.unwrap_or(Sample::SINGLE);
 
-        let size = self.window.inner_size();
+        let time = (self.time + (alpha - 1.0) * FIXED_TIMESTEP).max(0.0);
+        let delta_time = time - self.frame_time;
+        self.frame_time = time;
 
-        let game_info = GameInfo {
-            resolution: [size.width, size.height],
+        self.uniforms.game_info.set(GameInfo {
+            resolution: [self.surface_config.width, self.surface_config.height],
             time,
             delta_time,
-        };
+            jitter: jittered.jitter,
+            sample_index: jittered.index,
+            _padding: 0,
+        });
 
-        self.queue.write_buffer(
-            &self.uniform_buffers[0],
-            0,
-            bytemuck::cast_slice(&[game_info]),
-        );
-        self.queue.write_buffer(
-            &self.uniform_buffers[1],
-            0,
-            bytemuck::cast_slice(&[self.camera.uniform()]),
-        );
+        self.uniforms.game_info.flush(&self.queue);
+        self.uniforms.auto_exposure.flush(&self.queue);
+
+        sample
+    }
+
+    /// Uploads the world matrices of the nodes with a mesh, returning each
+    /// node with the offset of its matrix.
+    fn upload_models(&mut self) -> Vec<(NodeId, u32)> {
+        let models = &mut self.uniforms.models;
+        models.clear();
+        // Scenes have at most as many nodes with a mesh as the ring holds
+        let draws = self
+            .scene_graph
+            .nodes()
+            .filter(|(_, node)| node.mesh.is_some())
+            .map_while(|(id, _)| Some((id, models.push(self.scene_graph.uniform(id))?)))
+            .collect();
+        models.flush(&self.queue);
+        draws
     }
 
+    fn update_tone_mapping(&mut self) {
+        if let Some(pass) = self.post_chain.pass("tonemap") {
+            pass.set_params(
+                &self.queue,
+                bytemuck::bytes_of(&self.tone_mapping.uniform(self.surface_config.format)),
+            );
+        }
+    }
+
+    fn update_auto_exposure(&mut self) {
+        self.uniforms
+            .auto_exposure
+            .set(self.auto_exposure.uniform());
+    }
+
+    /// Derives the shared layouts from the shaders using them and binds the
+    /// resources, checking them against what the shaders declare.
     fn create_bind_groups(
         device: &wgpu::Device,
-        uniform_buffers: &[wgpu::Buffer],
-    ) -> (
-        HashMap<String, wgpu::BindGroupLayout>,
-        HashMap<String, wgpu::BindGroup>,
-    ) {
-        let game_info_bind_layout =
-            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-                label: Some("game_info_bind_layout"),
-                entries: &[
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 0,
-                        visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
-                        ty: wgpu::BindingType::Buffer {
-                            ty: wgpu::BufferBindingType::Uniform,
-                            has_dynamic_offset: false,
-                            min_binding_size: None,
-                        },
-                        count: None,
-                    },
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 1,
-                        visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
-                        ty: wgpu::BindingType::Buffer {
-                            ty: wgpu::BufferBindingType::Uniform,
-                            has_dynamic_offset: false,
-                            min_binding_size: None,
-                        },
-                        count: None,
-                    },
-                ],
-            });
-
-        let game_info_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: Some("game_info_bind_group"),
-            layout: &game_info_bind_layout,
-            entries: &[
-                wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: uniform_buffers[0].as_entire_binding(),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 1,
-                    resource: uniform_buffers[1].as_entire_binding(),
-                },
+        shaders: &ShaderLibrary,
+        uniforms: &Uniforms,
+        storage_buffers: &[wgpu::Buffer],
+        hdr_texture: &Texture,
+        white: &Texture,
+    ) -> anyhow::Result<(BindGroupLayouts, HashMap<String, wgpu::BindGroup>)> {
+        let scatter = shaders.reflection("scatter.wgsl", &[])?;
+        let diffuse = shaders.reflection("diffuse.wgsl", &[])?;
+        let defines = Self::exposure_defines();
+        let defines = defines
+            .each_ref()
+            .map(|(name, value)| (*name, value.as_str()));
+        let exposure = shaders.reflection("exposure.wgsl", &defines)?;
+
+        let mut layouts = BindGroupLayouts::default();
+        layouts.derive(
+            device,
+            "game_info",
+            &[(&scatter, 0), (&diffuse, 0), (&exposure, 0)],
+        )?;
+        layouts.derive(device, "exposure", &[(&exposure, 1)])?;
+        layouts.derive_dynamic(device, "model", &[(&diffuse, 1)])?;
+        layouts.derive(device, "material", &[(&diffuse, 2)])?;
+
+        let game_info_bind_group = layouts.create_bind_group(
+            device,
+            "game_info",
+            &[
+                uniforms.game_info.entry(0),
+                uniforms.camera.entry(1),
+                uniforms.atmosphere.entry(2),
             ],
-        });
+        )?;
 
-        let (mut layouts, mut groups) = (
-            HashMap::<String, wgpu::BindGroupLayout>::new(),
-            HashMap::<String, wgpu::BindGroup>::new(),
-        );
+        let model_bind_group =
+            layouts.create_bind_group(device, "model", &[uniforms.models.entry(0)])?;
+
+        // Bound for nodes without a material, or whose material has no texture
+        let default_material_bind_group =
+            Self::create_material_bind_group(device, &layouts, white)?;
 
-        layouts.insert("game_info".to_string(), game_info_bind_layout);
+        let exposure_bind_group = Self::create_exposure_bind_group(
+            device,
+            &layouts,
+            hdr_texture,
+            &uniforms.auto_exposure,
+            storage_buffers,
+        )?;
+
+        let mut groups = HashMap::<String, wgpu::BindGroup>::new();
         groups.insert("game_info".to_string(), game_info_bind_group);
+        groups.insert("exposure".to_string(), exposure_bind_group);
+        groups.insert("model".to_string(), model_bind_group);
+        groups.insert("default_material".to_string(), default_material_bind_group);
 
-        return (layouts, groups);
+        Ok((layouts, groups))
     }
 
-    #[allow(dead_code)]
-    fn create_screen_texture(
-        device: &wgpu::Device,
+    fn create_post_chain(
+        factory: &PipelineFactory,
         config: &wgpu::SurfaceConfiguration,
-    ) -> Texture {
-        let texture = device.create_texture(&wgpu::TextureDescriptor {
-            label: Some("screen_texture"),
-            size: wgpu::Extent3d {
-                width: config.width,
-                height: config.height,
-                depth_or_array_layers: 1,
+        hdr_texture: &Texture,
+        tone_mapping: &ToneMapping,
+        exposure_buffer: &wgpu::Buffer,
+        bloom: &Bloom,
+        shaders: &ShaderLibrary,
+    ) -> anyhow::Result<PostProcessChain> {
+        let device = factory.device;
+        let mut chain = PostProcessChain::new(
+            device,
+            shaders.load(device, "fullscreen.wgsl")?,
+            HDR_FORMAT,
+            config.format,
+        );
+
+        chain.add_pass(
+            device,
+            PostPassDescriptor {
+                label: "tonemap",
+                shader: shaders.load(device, "tonemap.wgsl")?,
+                params: bytemuck::bytes_of(&tone_mapping.uniform(config.format)),
             },
-            mip_level_count: 1,
-            sample_count: 1,
-            dimension: wgpu::TextureDimension::D2,
-            format: config.format,
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT
-                | wgpu::TextureUsages::COPY_SRC
-                | wgpu::TextureUsages::TEXTURE_BINDING,
-            view_formats: &[],
-        });
-        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
-        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
-            address_mode_u: wgpu::AddressMode::ClampToEdge,
-            address_mode_v: wgpu::AddressMode::ClampToEdge,
-            address_mode_w: wgpu::AddressMode::ClampToEdge,
-            mag_filter: wgpu::FilterMode::Linear,
-            min_filter: wgpu::FilterMode::Nearest,
-            mipmap_filter: wgpu::FilterMode::Nearest,
-            ..Default::default()
-        });
+            factory.cache,
+        )?;
 
-        Texture {
-            texture,
-            view,
-            sampler,
-        }
+        chain.resize(device, config, hdr_texture)?;
+        Self::update_tonemap_bind_group(device, &mut chain, exposure_buffer, bloom)?;
+
+        Ok(chain)
+    }
+
+    fn update_tonemap_bind_group(
+        device: &wgpu::Device,
+        chain: &mut PostProcessChain,
+        exposure_buffer: &wgpu::Buffer,
+        bloom: &Bloom,
+    ) -> anyhow::Result<()> {
+        let (bloom_texture, bloom_view) = bloom.output();
+        chain.create_extra_bind_group(
+            device,
+            "tonemap",
+            &[
+                Binding::buffer(0, exposure_buffer),
+                Binding::texture(1, bloom_texture, bloom_view),
+                Binding::buffer(2, bloom.uniform_buffer()),
+            ],
+        )
+    }
+
+    fn create_exposure_bind_group(
+        device: &wgpu::Device,
+        layouts: &BindGroupLayouts,
+        hdr_texture: &Texture,
+        auto_exposure: &UniformBuffer<AutoExposureUniform>,
+        storage_buffers: &[wgpu::Buffer],
+    ) -> anyhow::Result<wgpu::BindGroup> {
+        layouts.create_bind_group(
+            device,
+            "exposure",
+            &[
+                Binding::texture(0, &hdr_texture.texture, &hdr_texture.view),
+                Binding::buffer(1, &storage_buffers[0]),
+                auto_exposure.entry(2),
+                Binding::buffer(3, &storage_buffers[1]),
+            ],
+        )
+    }
+
+    fn create_material_bind_group(
+        device: &wgpu::Device,
+        layouts: &BindGroupLayouts,
+        texture: &Texture,
+    ) -> anyhow::Result<wgpu::BindGroup> {
+        layouts.create_bind_group(
+            device,
+            "material",
+            &[
+                Binding::texture(0, &texture.texture, &texture.view),
+                Binding::sampler(1, &texture.sampler),
+            ],
+        )
     }
 
-    fn create_meshes(device: &wgpu::Device) -> Vec<Mesh> {
-        let my_vertices = [
+    /// Creates the meshes of `scene`, loads its textures and binds those of its materials.
+    fn create_scene_resources(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        layouts: &BindGroupLayouts,
+        scene: &Scene,
+    ) -> anyhow::Result<SceneResources> {
+        let textures: HashMap<_, _> = scene
+            .textures
+            .iter()
+            .map(|desc| {
+                let image = png::read(&desc.path)?;
+                let texture = Texture::from_image(device, queue, &image, Some(&desc.name));
+                Ok((desc.name.clone(), texture))
+            })
+            .collect::<anyhow::Result<_>>()?;
+
+        // Materials without a texture fall back to the default bind group
+        let materials = scene
+            .materials
+            .iter()
+            .filter_map(|desc| Some((&desc.name, &textures[desc.texture.as_ref()?])))
+            .map(|(name, texture)| {
+                let bind_group = Self::create_material_bind_group(device, layouts, texture)?;
+                Ok((name.clone(), bind_group))
+            })
+            .collect::<anyhow::Result<_>>()?;
+
+        let meshes = scene
+            .meshes
+            .iter()
+            .map(|desc| {
+                let (vertices, indices) = match &desc.source {
+                    MeshSource::Quad => {
+                        // Texture rows go from the top down, as in OBJ models
+                        let (mut vertices, indices) = Self::quad();
+                        for vertex in &mut vertices {
+                            vertex.uv[1] = 1.0 - vertex.uv[1];
+                        }
+                        (vertices, indices)
+                    }
+                    MeshSource::Model(path) => obj::load(path)?,
+                };
+                Ok((desc.name.clone(), Mesh::create(device, &vertices, &indices)))
+            })
+            .collect::<anyhow::Result<_>>()?;
+
+        Ok(SceneResources { meshes, materials })
+    }
+
+    fn quad() -> (Vec<Vertex>, Vec<u32>) {
+        let my_vertices = vec![
             Vertex {
                 position: [-1.0, -1.0, 0.0],
                 uv: [0.0, 0.0],
@@ -326,187 +867,876 @@ impl MyGame<'_> {
                 uv: [0.0, 1.0],
             },
         ];
-        let indices = [0, 1, 2, 0, 2, 3];
-
-        let test_mesh = Mesh::create(device, &my_vertices, &indices);
+        let indices = vec![0, 1, 2, 0, 2, 3];
 
-        return vec![test_mesh];
+        (my_vertices, indices)
     }
 
     fn create_pipelines(
-        device: &wgpu::Device,
-        config: &wgpu::SurfaceConfiguration,
-        bind_group_layouts: &HashMap<String, wgpu::BindGroupLayout>,
-    ) -> Vec<wgpu::RenderPipeline> {
-        let _diffuse_module =
-            device.create_shader_module(wgpu::include_wgsl!("shaders/diffuse.wgsl"));
-        let scatter_module =
-            device.create_shader_module(wgpu::include_wgsl!("shaders/scatter.wgsl"));
-
-        // For pipelines that require access to camera features and model matrix
-        // Used in opaque and transparent passes
-        let world_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-            label: Some("world_layout"),
-            bind_group_layouts: &[&bind_group_layouts["game_info"]],
-            push_constant_ranges: &[],
-        });
+        factory: &PipelineFactory,
+        shaders: &ShaderLibrary,
+    ) -> anyhow::Result<HashMap<String, wgpu::RenderPipeline>> {
+        let scatter = shaders.load(factory.device, "scatter.wgsl")?;
+        let diffuse = shaders.load(factory.device, "diffuse.wgsl")?;
+
+        let scatter_pipeline = factory
+            .render("scatter_pipeline", &scatter)
+            .vertex_buffer(Vertex::desc())
+            // Running average: dst = src * w + dst * (1 - w), w set by blend constant
+            .target(HDR_FORMAT, Blend::Constant)
+            // Fills in the far plane wherever the scene left it
+            .depth(wgpu::CompareFunction::LessEqual, false)
+            .build()?;
+
+        let diffuse_pipeline = factory
+            .render("diffuse_pipeline", &diffuse)
+            .vertex_buffer(Vertex::desc())
+            .target(HDR_FORMAT, Blend::Constant)
+            // Quads are single-sided and models may wind either way
+            .cull_mode(None)
+            .build()?;
+
+        Ok(HashMap::from([
+            ("scatter".to_string(), scatter_pipeline),
+            ("diffuse".to_string(), diffuse_pipeline),
+        ]))
+    }
 
-        let scatter_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("scatter_pipeline"),
-            layout: Some(&world_layout),
-            vertex: wgpu::VertexState {
-                module: &scatter_module,
-                entry_point: Some("vs_main"),
-                compilation_options: wgpu::PipelineCompilationOptions::default(),
-                buffers: &[Vertex::desc()],
-            },
-            primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleList,
-                strip_index_format: None,
-                front_face: wgpu::FrontFace::Ccw,
-                cull_mode: Some(wgpu::Face::Back),
-                polygon_mode: wgpu::PolygonMode::Fill,
-                unclipped_depth: false,
-                conservative: false,
-            },
-            multisample: wgpu::MultisampleState {
-                count: 1,
-                mask: !0,
-                alpha_to_coverage_enabled: false,
-            },
-            fragment: Some(wgpu::FragmentState {
-                module: &scatter_module,
-                entry_point: Some("fs_main"),
-                compilation_options: wgpu::PipelineCompilationOptions::default(),
-                targets: &[Some(wgpu::ColorTargetState {
-                    format: config.format,
-                    blend: None,
-                    write_mask: wgpu::ColorWrites::ALL,
-                })],
-            }),
-            depth_stencil: Some(wgpu::DepthStencilState {
-                format: Texture::DEPTH_FORMAT,
-                depth_write_enabled: true,
-                depth_compare: wgpu::CompareFunction::Less,
-                stencil: wgpu::StencilState::default(),
-                bias: wgpu::DepthBiasState::default(),
-            }),
-            multiview: None,
-            cache: None,
+    /// Defines keeping exposure.wgsl in sync with the histogram constants.
+    fn exposure_defines() -> [(&'static str, String); 2] {
+        [
+            ("HISTOGRAM_BINS", format!("{HISTOGRAM_BINS}u")),
+            ("WORKGROUP_SIZE", format!("{HISTOGRAM_WORKGROUP_SIZE}u")),
+        ]
+    }
+
+    fn create_compute_pipelines(
+        factory: &PipelineFactory,
+        shaders: &ShaderLibrary,
+    ) -> anyhow::Result<HashMap<String, wgpu::ComputePipeline>> {
+        let defines = Self::exposure_defines();
+        let defines = defines
+            .each_ref()
+            .map(|(name, value)| (*name, value.as_str()));
+        let exposure = shaders.load_with(factory.device, "exposure.wgsl", &defines)?;
+
+        let histogram_pipeline = factory
+            .compute("histogram_pipeline", &exposure, "cs_histogram")
+            .build()?;
+        let average_pipeline = factory
+            .compute("average_luminance_pipeline", &exposure, "cs_average")
+            .build()?;
+
+        Ok(HashMap::from([
+            ("luminance_histogram".to_string(), histogram_pipeline),
+            ("average_luminance".to_string(), average_pipeline),
+        ]))
+    }
+
+    /// Checks the uniform structs uploaded by Rust against their WGSL declarations.
+    fn verify_uniform_layouts(shaders: &ShaderLibrary) -> anyhow::Result<()> {
+        let scatter = shaders.reflect("scatter.wgsl")?;
+        layout::verify::<GameInfo>(&scatter, "scatter.wgsl")?;
+        layout::verify::<CameraUniform>(&scatter, "scatter.wgsl")?;
+        layout::verify::<AtmosphereUniform>(&scatter, "scatter.wgsl")?;
+
+        let diffuse = shaders.reflect("diffuse.wgsl")?;
+        layout::verify::<GameInfo>(&diffuse, "diffuse.wgsl")?;
+        layout::verify::<CameraUniform>(&diffuse, "diffuse.wgsl")?;
+        layout::verify::<ModelUniform>(&diffuse, "diffuse.wgsl")?;
+
+        let exposure = shaders.reflect("exposure.wgsl")?;
+        layout::verify::<GameInfo>(&exposure, "exposure.wgsl")?;
+        layout::verify::<AutoExposureUniform>(&exposure, "exposure.wgsl")?;
+        layout::verify::<ExposureState>(&exposure, "exposure.wgsl")?;
+
+        let tonemap = shaders.reflect("tonemap.wgsl")?;
+        layout::verify::<ToneMappingUniform>(&tonemap, "tonemap.wgsl")?;
+        layout::verify::<ExposureState>(&tonemap, "tonemap.wgsl")?;
+        layout::verify::<BloomUniform>(&tonemap, "tonemap.wgsl")?;
+
+        let bloom = shaders.reflect("bloom.wgsl")?;
+        layout::verify::<BloomUniform>(&bloom, "bloom.wgsl")?;
+
+        Ok(())
+    }
+
+    /// Applies changes to the scene file and the files it uses, keeping the
+    /// current scene if the new one fails to load. The camera only moves if
+    /// the `[camera]` section changed, so that editing doesn't reset it.
+    fn reload_scene(&mut self) {
+        let Some(watcher) = &self.scene_watcher else {
+            return;
+        };
+        if !watcher.changed(&self.scene) {
+            return;
+        }
+
+        let path = watcher.path().to_path_buf();
+        let loaded = Scene::load(Some(&path)).and_then(|scene| {
+            let resources = Self::create_scene_resources(
+                &self.device,
+                &self.queue,
+                &self.bind_group_layouts,
+                &scene,
+            )?;
+            Ok((scene, resources))
         });
+        let (scene, scene_resources) = match loaded {
+            Ok(loaded) => loaded,
+            Err(e) => {
+                log::error!("Failed to reload the scene, keeping the previous one:\n{e:#}");
+                return;
+            }
+        };
 
-        return vec![scatter_pipeline];
+        if scene.camera != self.scene.camera {
+            self.camera_controller
+                .set_pose(&mut self.camera, &scene.camera);
+            self.previous_camera = self.camera.clone();
+        }
+        if scene.atmosphere != self.scene.atmosphere {
+            self.atmosphere = scene.atmosphere.clone();
+        }
+        self.scene_resources = scene_resources;
+        self.scene_graph = SceneGraph::from_scene(&scene);
+        self.scene = scene;
+        self.accumulator.reset();
+        log::info!("Reloaded {}.", path.display());
     }
 
+    /// Rebuilds the pipelines using shaders modified on disk. Pipelines whose
+    /// shaders fail to compile are kept as they were.
+    fn reload_shaders(&mut self) {
+        let changed = self.shaders.changed();
+        if changed.is_empty() {
+            return;
+        }
+
+        if let Err(e) = Self::verify_uniform_layouts(&self.shaders) {
+            log::error!("Not reloading shaders:\n{e:#}");
+            return;
+        }
+
+        let report = |name: &str, result: anyhow::Result<()>| match result {
+            Ok(()) => log::info!("Reloaded {name}."),
+            Err(e) => {
+                log::error!("Failed to reload {name}, keeping the previous pipelines:\n{e:#}")
+            }
+        };
+
+        let factory = PipelineFactory {
+            device: &self.device,
+            layouts: &self.bind_group_layouts,
+            surface_format: self.surface_config.format,
+            cache: self.pipeline_cache.get(),
+        };
+
+        if changed.contains("scatter.wgsl") || changed.contains("diffuse.wgsl") {
+            let pipelines = Self::create_pipelines(&factory, &self.shaders);
+            report(
+                "scatter.wgsl and diffuse.wgsl",
+                pipelines.map(|pipelines| {
+                    self.pipelines = pipelines;
+                    self.accumulator.reset();
+                }),
+            );
+        }
+
+        if changed.contains("exposure.wgsl") {
+            let pipelines = Self::create_compute_pipelines(&factory, &self.shaders);
+            report(
+                "exposure.wgsl",
+                pipelines.map(|pipelines| self.compute_pipelines = pipelines),
+            );
+        }
+
+        if changed.contains("bloom.wgsl") {
+            let module = self.shaders.load(&self.device, "bloom.wgsl");
+            report(
+                "bloom.wgsl",
+                module.and_then(|shader| self.bloom.reload(&self.device, &shader, factory.cache)),
+            );
+        }
+
+        if changed.contains("tonemap.wgsl") {
+            let module = self.shaders.load(&self.device, "tonemap.wgsl");
+            report(
+                "tonemap.wgsl",
+                module.and_then(|shader| {
+                    self.post_chain
+                        .reload_pass(&self.device, "tonemap", shader, factory.cache)
+                }),
+            );
+        }
+
+        if changed.contains("fullscreen.wgsl") {
+            let module = self.shaders.load(&self.device, "fullscreen.wgsl");
+            report(
+                "fullscreen.wgsl",
+                module.and_then(|shader| {
+                    self.post_chain
+                        .reload_vertex(&self.device, shader, factory.cache)
+                }),
+            );
+        }
+
+        if let Some(debug_view) = &mut self.debug_view {
+            if changed.contains("debug_view.wgsl") {
+                report(
+                    "debug_view.wgsl",
+                    debug_view.reload(&self.device, &self.shaders, factory.cache),
+                );
+            }
+        }
+    }
+
+    /// Recreates the device and every GPU resource after the device was lost,
+    /// carrying over the camera, settings and shaders.
+    fn recover_device(&mut self) -> anyhow::Result<()> {
+        log::warn!("Recreating the device and GPU resources.");
+
+        {
+            let size = PhysicalSize::new(self.surface_config.width, self.surface_config.height);
+            let recreated =
+                Self::create(self.window.clone(), size, &self.options, self.scene.clone())
+                    .block_on()?;
+            let old = std::mem::replace(self, recreated);
+
+            self.time = old.time;
+            self.frame_time = old.frame_time;
+            self.paused = old.paused;
+            self.occluded = old.occluded;
+            self.previous_camera = old.previous_camera;
+            self.camera = old.camera;
+            self.camera_controller = old.camera_controller;
+            self.atmosphere = old.atmosphere;
+            self.benchmark = old.benchmark;
+            self.shaders = old.shaders;
+            self.exposure_state = old.exposure_state;
+            self.accumulator.enabled = old.accumulator.enabled;
+            self.tone_mapping = old.tone_mapping;
+            self.auto_exposure = old.auto_exposure;
+            self.bloom.settings = old.bloom.settings;
+            self.config = old.config;
+            self.frame_limiter = old.frame_limiter;
+            self.surface_config.present_mode =
+                present::supported(old.surface_config.present_mode, &self.present_modes);
+            // The old surfaces are dropped here, releasing their swapchains. The
+            // debug view is closed along with its own as it used the old device
+        }
+
+        self.configure_surface();
+        self.update_tone_mapping();
+        self.update_auto_exposure();
+        self.bloom.update_settings(&self.queue);
+        Ok(())
+    }
+
+    fn configure_surface(&self) {
+        if let Some(surface) = self.surface.get() {
+            surface.configure(&self.device, &self.surface_config);
+        }
+    }
+
+    /// Pauses when the window can't be seen and resumes when it's back.
+    fn update_paused(&mut self) {
+        let Some(window) = &self.window else {
+            return;
+        };
+        let size = window.inner_size();
+        let visible = self.surface.get().is_some()
+            && size.width > 0
+            && size.height > 0
+            && !self.occluded
+            && !window.is_minimized().unwrap_or(false);
+
+        if self.paused == visible {
+            log::info!(
+                "Window {}.",
+                if visible {
+                    "visible, resuming"
+                } else {
+                    "hidden, pausing"
+                }
+            );
+            self.paused = !visible;
+        }
+    }
+
+    /// Reconfigures the surface and size dependent resources. Zero sizes from
+    /// minimising are ignored, keeping the previous configuration until restored.
     fn resize(&mut self, new_size: PhysicalSize<u32>) {
+        if new_size.width == 0 || new_size.height == 0 {
+            return;
+        }
+
         self.surface_config.width = new_size.width;
         self.surface_config.height = new_size.height;
-        self.surface.configure(&self.device, &self.surface_config);
+        self.configure_surface();
 
-        self.depth_texture = Texture::create_depth_texture(
+        self.hdr_texture = Texture::create_render_target(
             &self.device,
             &self.surface_config,
-            Some("depth_texture"),
+            HDR_FORMAT,
+            Some("hdr_texture"),
         );
-        // self.screen_texture = Self::create_screen_texture(&self.device, &self.surface_config);
+        self.bloom
+            .resize(&self.device, &self.surface_config, &self.hdr_texture)
+            .expect("Bloom resources were checked at startup");
+        self.post_chain
+            .resize(&self.device, &self.surface_config, &self.hdr_texture)
+            .expect("Post processing resources were checked at startup");
+        Self::update_tonemap_bind_group(
+            &self.device,
+            &mut self.post_chain,
+            &self.storage_buffers[1],
+            &self.bloom,
+        )
+        .expect("Tone mapping resources were checked at startup");
+        let exposure_bind_group = Self::create_exposure_bind_group(
+            &self.device,
+            &self.bind_group_layouts,
+            &self.hdr_texture,
+            &self.uniforms.auto_exposure,
+            &self.storage_buffers,
+        )
+        .expect("Exposure resources were checked at startup");
+        self.bind_groups
+            .insert("exposure".to_string(), exposure_bind_group);
+        self.accumulator.reset();
     }
 
-    fn update(&mut self, delta: f32) {
-        self.camera_controller.update(&mut self.camera, delta);
+    /// Remembers where the window is for the next run. Windowed geometry is only
+    /// remembered while it's not overridden by fullscreen or maximizing, so
+    /// leaving either restores the old one.
+    fn save_window_geometry(&mut self, window: &Window) {
+        let fullscreen = window.fullscreen().is_some();
+        self.config.set("fullscreen", fullscreen);
+        if let Some(monitor) = window.current_monitor().filter(|_| fullscreen) {
+            if let Some(index) = window.available_monitors().position(|m| m == monitor) {
+                self.config.set("monitor", index);
+            }
+        }
+        if !fullscreen && !window.is_maximized() && !window.is_minimized().unwrap_or(false) {
+            let size = window.inner_size();
+            self.config
+                .set("window_size", format!("{}x{}", size.width, size.height));
+            if let Ok(position) = window.outer_position() {
+                self.config
+                    .set("window_position", format!("{},{}", position.x, position.y));
+            }
+        }
     }
 
-    fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
-        let time = (std::time::Instant::now() - self.start_time).as_secs_f32();
-        let delta = time - self.prev_time;
-        self.update(delta);
-        self.update_uniform_buffers();
-        self.prev_time = time;
+    fn toggle_debug_view(&mut self, event_loop: &winit::event_loop::ActiveEventLoop) {
+        if self.debug_view.take().is_some() {
+            log::info!("Debug view closed.");
+            return;
+        }
 
-        let image = self.surface.get_current_texture()?;
+        match DebugView::open(
+            event_loop,
+            &self.instance,
+            &self.adapter,
+            &self.device,
+            &self.shaders,
+            self.pipeline_cache.get(),
+        ) {
+            Ok(debug_view) => {
+                log::info!("Debug view showing the {}.", debug_view.source.name());
+                self.debug_view = Some(debug_view);
+            }
+            Err(e) => log::error!("Failed to open the debug view: {e:#}"),
+        }
+    }
+
+    /// Handles the events of the debug view window, Tab switches what it shows.
+    fn debug_view_event(&mut self, event: WindowEvent) {
+        let Some(debug_view) = &mut self.debug_view else {
+            return;
+        };
+
+        match event {
+            WindowEvent::Resized(new_size) => debug_view.resize(&self.device, new_size),
+            WindowEvent::KeyboardInput { event, .. } => {
+                if event.physical_key == KeyCode::Tab && event.state.is_pressed() {
+                    debug_view.source = debug_view.source.next();
+                    log::info!("Debug view showing the {}.", debug_view.source.name());
+                }
+
+                if event.physical_key == KeyCode::Escape && event.state.is_pressed() {
+                    self.debug_view = None;
+                    log::info!("Debug view closed.");
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn draw(&mut self, alpha: f32) -> Result<(), wgpu::SurfaceError> {
+        let Some(surface) = self.surface.get() else {
+            return Ok(());
+        };
+        let image = surface.get_current_texture()?;
 
         let view = image
             .texture
             .create_view(&wgpu::TextureViewDescriptor::default());
 
+        self.render_frame(alpha, &view);
+
+        image.present();
+
+        if let Some(debug_view) = &self.debug_view {
+            debug_view.request_redraw();
+        }
+
+        Ok(())
+    }
+
+    /// Renders a frame interpolated by `alpha` between the last two updates to
+    /// `view`, which has the format of the surface configuration.
+    fn render_frame(&mut self, alpha: f32, view: &wgpu::TextureView) {
+        self.reload_shaders();
+        self.reload_scene();
+
+        let sample = self.update_uniform_buffers(alpha);
+        let draws = self.upload_models();
+
         let mut encoder = self
             .device
             .create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
 
-        {
-            let mut opaque_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("opaque_pass"),
-                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &view,
-                    resolve_target: None,
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Load,
-                        store: wgpu::StoreOp::Store,
-                    },
-                })],
-                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
-                    view: &self.depth_texture.view,
-                    depth_ops: Some(wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(1.0),
-                        store: wgpu::StoreOp::Store,
-                    }),
-                    stencil_ops: None,
-                }),
-                timestamp_writes: None,
-                occlusion_query_set: None,
+        let exposure_copied = self.exposure_readback.begin();
+        let mut transient_pool = std::mem::take(&mut self.transient_pool);
+        let this = &*self;
+
+        let mut graph = RenderGraph::new();
+        let hdr = graph.import_texture("hdr", &this.hdr_texture.view);
+        let bloom = graph.import("bloom");
+        let exposure = graph.import("exposure_state");
+        let surface = graph.import_texture("surface", view);
+        graph.mark_output(surface);
+
+        if let Some(sample) = sample {
+            let depth = graph.create_texture(TransientDesc {
+                label: "depth_texture",
+                size: (this.surface_config.width, this.surface_config.height),
+                format: Texture::DEPTH_FORMAT,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
             });
 
-            opaque_pass.set_pipeline(&self.pipelines[0]);
+            graph
+                .add_pass("opaque")
+                .read(hdr)
+                .write(hdr)
+                .write(depth)
+                .execute(move |encoder, resources| {
+                    let mut opaque_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                        label: Some("opaque_pass"),
+                        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                            view: resources.view(hdr),
+                            resolve_target: None,
+                            ops: wgpu::Operations {
+                                // The first sample starts over, so nothing is left
+                                // behind where nodes no longer cover the target
+                                load: if sample.index == 0 {
+                                    wgpu::LoadOp::Clear(wgpu::Color::BLACK)
+                                } else {
+                                    wgpu::LoadOp::Load
+                                },
+                                store: wgpu::StoreOp::Store,
+                            },
+                        })],
+                        depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                            view: resources.view(depth),
+                            depth_ops: Some(wgpu::Operations {
+                                load: wgpu::LoadOp::Clear(1.0),
+                                store: wgpu::StoreOp::Store,
+                            }),
+                            stencil_ops: None,
+                        }),
+                        timestamp_writes: None,
+                        occlusion_query_set: None,
+                    });
 
-            opaque_pass.set_bind_group(0, self.bind_groups.get("game_info"), &[]);
+                    opaque_pass.set_blend_constant(wgpu::Color {
+                        r: sample.weight,
+                        g: sample.weight,
+                        b: sample.weight,
+                        a: sample.weight,
+                    });
 
-            self.meshes[0].draw(&mut opaque_pass);
+                    opaque_pass.set_bind_group(0, this.bind_groups.get("game_info"), &[]);
+
+                    opaque_pass.set_pipeline(&this.pipelines["diffuse"]);
+                    for (node, offset) in draws {
+                        let node = this.scene_graph.node(node);
+                        let Some(mesh) = node
+                            .mesh
+                            .as_ref()
+                            .and_then(|name| this.scene_resources.meshes.get(name))
+                        else {
+                            continue;
+                        };
+                        let material = node
+                            .material
+                            .as_ref()
+                            .and_then(|name| this.scene_resources.materials.get(name))
+                            .or(this.bind_groups.get("default_material"));
+                        opaque_pass.set_bind_group(1, this.bind_groups.get("model"), &[offset]);
+                        opaque_pass.set_bind_group(2, material, &[]);
+                        mesh.draw(&mut opaque_pass);
+                    }
+
+                    // The atmosphere goes behind everything drawn so far
+                    opaque_pass.set_pipeline(&this.pipelines["scatter"]);
+                    this.screen_quad.draw(&mut opaque_pass);
+                });
         }
 
-        self.queue.submit(std::iter::once(encoder.finish()));
+        graph
+            .add_pass("exposure")
+            .read(hdr)
+            .write(exposure)
+            .execute(|encoder, _| {
+                let mut exposure_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                    label: Some("exposure_pass"),
+                    timestamp_writes: None,
+                });
+
+                exposure_pass.set_bind_group(0, this.bind_groups.get("game_info"), &[]);
+                exposure_pass.set_bind_group(1, this.bind_groups.get("exposure"), &[]);
+
+                exposure_pass.set_pipeline(&this.compute_pipelines["luminance_histogram"]);
+                exposure_pass.dispatch_workgroups(
+                    this.surface_config.width.div_ceil(HISTOGRAM_WORKGROUP_SIZE),
+                    this.surface_config
+                        .height
+                        .div_ceil(HISTOGRAM_WORKGROUP_SIZE),
+                    1,
+                );
+
+                exposure_pass.set_pipeline(&this.compute_pipelines["average_luminance"]);
+                exposure_pass.dispatch_workgroups(1, 1, 1);
+            });
 
-        image.present();
+        graph
+            .add_pass("bloom")
+            .read(hdr)
+            .write(bloom)
+            .execute(|encoder, _| this.bloom.render(encoder));
+
+        if exposure_copied {
+            graph
+                .add_pass("exposure_readback")
+                .read(exposure)
+                .side_effect()
+                .execute(|encoder, _| {
+                    this.exposure_readback
+                        .copy(encoder, &this.storage_buffers[1])
+                });
+        }
 
-        Ok(())
+        graph
+            .add_pass("post")
+            .read(hdr)
+            .read(bloom)
+            .read(exposure)
+            .write(surface)
+            .execute(|encoder, resources| this.post_chain.render(encoder, resources.view(surface)));
+
+        if let Err(e) = graph.execute(&this.device, &mut encoder, &mut transient_pool) {
+            log::error!("Skipping the frame:\n{e:#}");
+        }
+        self.transient_pool = transient_pool;
+
+        self.queue.submit(std::iter::once(encoder.finish()));
+
+        if exposure_copied {
+            self.exposure_readback.map();
+        }
+        self.device.poll(wgpu::Maintain::Poll);
+        if let Some(state) = self.exposure_readback.poll() {
+            self.exposure_state = state;
+        }
     }
 }
 
 impl Game for MyGame<'_> {
-    fn init(window: Arc<Window>) -> Self {
-        Self::new(window).block_on()
+    type Options = Options;
+
+    fn init(window: Arc<Window>, options: &Options) -> anyhow::Result<Self> {
+        Self::new(window, options).block_on()
+    }
+
+    /// Geometry saved by the last run, unless overridden on the command line.
+    fn window_config(options: &Options) -> WindowConfig {
+        let config = &options.config;
+
+        WindowConfig {
+            title: "Scatter".to_string(),
+            size: options
+                .size
+                .or_else(|| config.parse("window_size", parse_size)),
+            position: config.parse("window_position", |value| {
+                let (x, y) = value.split_once(',')?;
+                Some(PhysicalPosition::new(
+                    x.trim().parse().ok()?,
+                    y.trim().parse().ok()?,
+                ))
+            }),
+            fullscreen: options
+                .fullscreen
+                .or_else(|| config.parse("fullscreen", |value| value.parse().ok()))
+                .unwrap_or(false),
+            monitor: config.parse("monitor", |value| value.parse().ok()),
+            ..Default::default()
+        }
+    }
+
+    fn update(&mut self, dt: f32) {
+        self.time += dt;
+        self.previous_camera = self.camera.clone();
+        self.camera_controller.update(&mut self.camera, dt);
+
+        self.scene_graph.animate(dt);
+        if self.scene_graph.update() {
+            self.accumulator.reset();
+        }
+    }
+
+    fn render(&mut self, event_loop: &winit::event_loop::ActiveEventLoop, alpha: f32) {
+        if self.device_lost.load(Ordering::Acquire) {
+            if let Err(e) = self.recover_device() {
+                Self::error(e.context("Failed to recover from losing the device"));
+                event_loop.exit();
+                return;
+            }
+        }
+
+        match self.draw(alpha) {
+            Ok(()) => {}
+            Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) => {
+                self.configure_surface();
+            }
+            Err(wgpu::SurfaceError::Timeout) => {
+                log::warn!("Timed out waiting for the next frame, skipping it.");
+            }
+            Err(wgpu::SurfaceError::OutOfMemory) => {
+                log::error!("Out of memory while acquiring the next frame, exiting.");
+                event_loop.exit();
+            }
+        }
+
+        if self.benchmark.as_mut().is_some_and(Benchmark::frame) {
+            println!("{}", self.benchmark.as_ref().unwrap().report());
+            event_loop.exit();
+        }
+
+        self.frame_limiter.wait();
+    }
+
+    fn suspended(&mut self, _event_loop: &winit::event_loop::ActiveEventLoop) {
+        log::info!("Suspended, releasing the surface.");
+        self.debug_view = None;
+        self.surface.suspend();
+        self.update_paused();
+    }
+
+    fn resumed(&mut self, _event_loop: &winit::event_loop::ActiveEventLoop) -> anyhow::Result<()> {
+        let Some(window) = self.window.clone() else {
+            return Ok(());
+        };
+
+        let resumed = self.surface.resume(|| {
+            let surface = self
+                .instance
+                .create_surface(window.clone())
+                .context("Failed to recreate the surface")?;
+
+            // Pipelines were built for the old format, the rest may change freely
+            let formats = surface.get_capabilities(&self.adapter).formats;
+            anyhow::ensure!(
+                formats.contains(&self.surface_config.format),
+                "The new surface doesn't support {:?}",
+                self.surface_config.format
+            );
+            Ok(surface)
+        })?;
+        let Some(surface) = resumed else {
+            return Ok(());
+        };
+
+        self.present_modes = surface.get_capabilities(&self.adapter).present_modes;
+        self.surface_config.present_mode =
+            present::supported(self.surface_config.present_mode, &self.present_modes);
+
+        log::info!("Resumed, recreated the surface.");
+        // The window may have been resized or rotated in the meantime
+        self.resize(window.inner_size());
+        self.update_paused();
+        Ok(())
+    }
+
+    fn render_window(
+        &mut self,
+        _event_loop: &winit::event_loop::ActiveEventLoop,
+        window_id: winit::window::WindowId,
+    ) {
+        let Some(debug_view) = self.debug_view.as_ref().filter(|v| v.id() == window_id) else {
+            return;
+        };
+
+        let (texture, view) = match debug_view.source {
+            DebugSource::Hdr => (&self.hdr_texture.texture, &self.hdr_texture.view),
+            DebugSource::Bloom => self.bloom.output(),
+        };
+        if let Err(e) = debug_view.render(&self.device, &self.queue, texture, view) {
+            log::warn!("Failed to draw the debug view: {e:#}");
+        }
+    }
+
+    fn window_closed(&mut self, window_id: winit::window::WindowId) {
+        if self
+            .debug_view
+            .as_ref()
+            .is_some_and(|v| v.id() == window_id)
+        {
+            self.debug_view = None;
+            log::info!("Debug view closed.");
+        }
     }
 
     fn window_event(
         &mut self,
         event_loop: &winit::event_loop::ActiveEventLoop,
-        _window_id: winit::window::WindowId,
+        window_id: winit::window::WindowId,
         event: winit::event::WindowEvent,
     ) {
+        let Some(window) = self.window.clone() else {
+            return;
+        };
+        if window_id != window.id() {
+            self.debug_view_event(event);
+            return;
+        }
+
         self.camera_controller.process_window_events(&event);
         match event {
             WindowEvent::CloseRequested => event_loop.exit(),
-            WindowEvent::RedrawRequested => match self.render() {
-                Ok(()) => {}
-                Err(wgpu::SurfaceError::Lost) => todo!("Surface error"),
-                Err(e) => panic!("Error while trying to render: {e}"),
-            },
             WindowEvent::Resized(new_size) => {
+                self.update_paused();
                 self.resize(new_size);
             }
+            WindowEvent::Occluded(occluded) => {
+                self.occluded = occluded;
+                self.update_paused();
+            }
             WindowEvent::KeyboardInput { event, .. } => {
                 if event.physical_key == KeyCode::Escape && event.state.is_pressed() {
                     event_loop.exit();
                 }
 
+                if event.physical_key == KeyCode::KeyP && event.state.is_pressed() {
+                    self.accumulator.toggle();
+                    log::info!(
+                        "Progressive accumulation {}.",
+                        if self.accumulator.enabled {
+                            "enabled"
+                        } else {
+                            "disabled"
+                        }
+                    );
+                }
+
+                if event.physical_key == KeyCode::KeyT && event.state.is_pressed() {
+                    self.tone_mapping.operator = self.tone_mapping.operator.next();
+                    log::info!("Tone mapper: {:?}", self.tone_mapping.operator);
+                    self.update_tone_mapping();
+                }
+
+                if event.physical_key == KeyCode::KeyE && event.state.is_pressed() {
+                    self.auto_exposure.enabled = !self.auto_exposure.enabled;
+                    log::info!(
+                        "Auto exposure {} (average log2 luminance {:.2}, exposure {:+.2} EV).",
+                        if self.auto_exposure.enabled {
+                            "enabled"
+                        } else {
+                            "disabled"
+                        },
+                        self.exposure_state.average_log_luminance,
+                        self.exposure_state.exposure_ev
+                    );
+                    self.update_auto_exposure();
+                }
+
+                if event.physical_key == KeyCode::KeyB && event.state.is_pressed() {
+                    self.bloom.settings.enabled = !self.bloom.settings.enabled;
+                    log::info!(
+                        "Bloom {}.",
+                        if self.bloom.settings.enabled {
+                            "enabled"
+                        } else {
+                            "disabled"
+                        }
+                    );
+                    self.bloom.update_settings(&self.queue);
+                }
+
+                if event.physical_key == KeyCode::Equal && event.state.is_pressed() {
+                    self.tone_mapping.exposure_ev += 0.5;
+                    log::info!("Exposure: {:+.1} EV", self.tone_mapping.exposure_ev);
+                    self.update_tone_mapping();
+                }
+
+                if event.physical_key == KeyCode::Minus && event.state.is_pressed() {
+                    self.tone_mapping.exposure_ev -= 0.5;
+                    log::info!("Exposure: {:+.1} EV", self.tone_mapping.exposure_ev);
+                    self.update_tone_mapping();
+                }
+
+                if event.physical_key == KeyCode::KeyV && event.state.is_pressed() {
+                    let mode = present::next(self.surface_config.present_mode, &self.present_modes);
+                    self.surface_config.present_mode = mode;
+                    self.configure_surface();
+                    self.config.set("present_mode", present::name(mode));
+                    log::info!("Present mode: {}", present::name(mode));
+                }
+
+                if event.physical_key == KeyCode::KeyL && event.state.is_pressed() {
+                    self.frame_limiter.cycle();
+                    let max_fps = self.frame_limiter.max_fps();
+                    self.config.set("max_fps", max_fps.unwrap_or(0));
+                    match max_fps {
+                        Some(fps) => log::info!("Frame rate limited to {fps} FPS."),
+                        None => log::info!("Frame rate limit disabled."),
+                    }
+                }
+
+                // Simulates the suspend and resume of mobile platforms
+                if event.physical_key == KeyCode::F9 && event.state.is_pressed() {
+                    if !self.surface.is_suspended() {
+                        self.suspended(event_loop);
+                    } else if let Err(e) = Game::resumed(self, event_loop) {
+                        log::error!("{e:#}");
+                        event_loop.exit();
+                    }
+                }
+
+                if event.physical_key == KeyCode::F3 && event.state.is_pressed() {
+                    self.toggle_debug_view(event_loop);
+                }
+
                 if event.physical_key == KeyCode::KeyF && event.state.is_pressed() {
-                    self.window.set_fullscreen(match self.window.fullscreen() {
+                    window.set_fullscreen(match window.fullscreen() {
                         Some(_) => None,
-                        None => Some(winit::window::Fullscreen::Borderless(None))
+                        None => Some(winit::window::Fullscreen::Borderless(None)),
                     });
                 }
             }
-            _ => {
-
-            }
+            _ => {}
         }
     }
 
@@ -519,7 +1749,32 @@ impl Game for MyGame<'_> {
         self.camera_controller.process_device_events(&event);
     }
 
-    fn about_to_wait(&mut self, _event_loop: &winit::event_loop::ActiveEventLoop) {
-        self.window.request_redraw();
+    fn paused(&self) -> bool {
+        self.paused
+    }
+
+    fn exiting(&mut self, _event_loop: &winit::event_loop::ActiveEventLoop) {
+        if let Some(window) = self.window.clone() {
+            self.save_window_geometry(&window);
+        }
+
+        if let Err(e) = self.pipeline_cache.save(&self.shaders) {
+            log::warn!("Failed to save the pipeline cache: {e:#}");
+        }
+        if let Err(e) = self.config.save() {
+            log::warn!("Failed to save settings: {e:#}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn uniform_layouts_match_shaders() {
+        if let Err(e) = MyGame::verify_uniform_layouts(&ShaderLibrary::new()) {
+            panic!("{e:#}");
+        }
     }
 }
@@ -0,0 +1,85 @@
+/// Progressive accumulation of jittered samples while the camera is stationary.
+///
+/// Every sample is blended into a floating-point accumulation texture with
+/// weight `1 / (n + 1)`, so the texture always holds the running average.
+pub struct Accumulator {
+    pub enabled: bool,
+    pub max_samples: u32,
+    sample_count: u32,
+}
+
+/// Parameters of a single accumulated sample.
+#[derive(Clone, Copy, Debug)]
+pub struct Sample {
+    pub index: u32,
+    /// Sub-pixel offset in pixels, in range [-0.5, 0.5).
+    pub jitter: [f32; 2],
+    /// Blend weight of the new sample against the accumulated average.
+    pub weight: f64,
+}
+
+impl Sample {
+    pub const SINGLE: Sample = Sample {
+        index: 0,
+        jitter: [0.0, 0.0],
+        weight: 1.0,
+    };
+}
+
+impl Accumulator {
+    pub fn new(max_samples: u32) -> Self {
+        Self {
+            enabled: false,
+            max_samples,
+            sample_count: 0,
+        }
+    }
+
+    pub fn toggle(&mut self) {
+        self.enabled = !self.enabled;
+        self.reset();
+    }
+
+    /// Discards accumulated samples. Must be called whenever the image changes.
+    pub fn reset(&mut self) {
+        self.sample_count = 0;
+    }
+
+    /// Returns the next sample to render, or `None` when the image has converged.
+    pub fn next_sample(&mut self) -> Option<Sample> {
+        if !self.enabled {
+            return Some(Sample::SINGLE);
+        }
+
+        if self.sample_count >= self.max_samples {
+            return None;
+        }
+
+        let index = self.sample_count;
+        self.sample_count += 1;
+
+        if self.sample_count == self.max_samples {
+            log::info!("Accumulation converged after {} samples.", self.max_samples);
+        }
+
+        Some(Sample {
+            index,
+            jitter: [halton(index + 1, 2) - 0.5, halton(index + 1, 3) - 0.5],
+            weight: 1.0 / (index + 1) as f64,
+        })
+    }
+}
+
+/// Radical inverse of `index` in the given base.
+fn halton(mut index: u32, base: u32) -> f32 {
+    let mut result = 0.0;
+    let mut fraction = 1.0;
+
+    while index > 0 {
+        fraction /= base as f32;
+        result += fraction * (index % base) as f32;
+        index /= base;
+    }
+
+    result
+}
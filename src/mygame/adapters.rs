@@ -0,0 +1,238 @@
+use std::fmt::Write;
+
+use anyhow::Context;
+
+/// Adapter picked by [`GpuOptions::adapter`] instead of the preferred one.
+#[derive(Clone, Debug)]
+pub enum AdapterSelector {
+    /// Position in the list printed by `--list-adapters`.
+    Index(usize),
+    /// Case insensitive part of the adapter name.
+    Name(String),
+}
+
+/// How the instance is created and the adapter chosen.
+///
+/// Defaults can be overridden by `SCATTER_BACKEND`, `SCATTER_POWER`,
+/// `SCATTER_ADAPTER` and `SCATTER_VALIDATION`, which command line options
+/// override in turn, see [`Self::set`] for the accepted values.
+#[derive(Clone, Debug)]
+pub struct GpuOptions {
+    pub backends: wgpu::Backends,
+    /// Only consider software adapters such as WARP or llvmpipe.
+    pub software: bool,
+    pub power_preference: wgpu::PowerPreference,
+    pub adapter: Option<AdapterSelector>,
+    pub validation: bool,
+}
+
+impl Default for GpuOptions {
+    fn default() -> Self {
+        Self {
+            backends: wgpu::Backends::VULKAN | wgpu::Backends::DX12,
+            software: false,
+            power_preference: wgpu::PowerPreference::HighPerformance,
+            adapter: None,
+            validation: true,
+        }
+    }
+}
+
+impl GpuOptions {
+    /// Names of the settings, each also read from `SCATTER_<NAME>`.
+    pub const KEYS: [&'static str; 4] = ["backend", "power", "adapter", "validation"];
+
+    pub fn from_env() -> anyhow::Result<Self> {
+        let mut options = Self::default();
+        for key in Self::KEYS {
+            let var = format!("SCATTER_{}", key.to_uppercase());
+            if let Ok(value) = std::env::var(&var) {
+                options
+                    .set(key, &value)
+                    .with_context(|| format!("Invalid {var}"))?;
+            }
+        }
+        Ok(options)
+    }
+
+    /// Changes a setting:
+    /// - `backend`: comma separated `vulkan`, `dx12`, `metal`, `gl`, `all` or
+    ///   `software`, the last alone meaning a software adapter on any backend
+    /// - `power`: `high`, `low` or `none`
+    /// - `adapter`: index or part of the name of an adapter
+    /// - `validation`: `on` or `off`
+    pub fn set(&mut self, key: &str, value: &str) -> anyhow::Result<()> {
+        match key {
+            "backend" => {
+                let mut backends = wgpu::Backends::empty();
+                let mut software = false;
+                for name in value.split(',').map(|name| name.trim().to_lowercase()) {
+                    backends |= match name.as_str() {
+                        "vulkan" | "vk" => wgpu::Backends::VULKAN,
+                        "dx12" | "d3d12" => wgpu::Backends::DX12,
+                        "metal" | "mtl" => wgpu::Backends::METAL,
+                        "gl" | "gles" | "opengl" => wgpu::Backends::GL,
+                        "all" => wgpu::Backends::all(),
+                        "software" | "fallback" => {
+                            software = true;
+                            continue;
+                        }
+                        _ => anyhow::bail!("unknown backend {name:?}"),
+                    };
+                }
+                self.backends = if backends.is_empty() {
+                    wgpu::Backends::all()
+                } else {
+                    backends
+                };
+                self.software = software;
+            }
+            "power" => {
+                self.power_preference = match value.to_lowercase().as_str() {
+                    "high" | "high-performance" => wgpu::PowerPreference::HighPerformance,
+                    "low" | "low-power" => wgpu::PowerPreference::LowPower,
+                    "none" => wgpu::PowerPreference::None,
+                    _ => anyhow::bail!("unknown power preference {value:?}"),
+                }
+            }
+            "adapter" => {
+                self.adapter = Some(match value.parse() {
+                    Ok(index) => AdapterSelector::Index(index),
+                    Err(_) => AdapterSelector::Name(value.to_lowercase()),
+                })
+            }
+            "validation" => {
+                self.validation = match value.to_lowercase().as_str() {
+                    "on" | "true" | "1" => true,
+                    "off" | "false" | "0" => false,
+                    _ => anyhow::bail!("expected on or off for validation, got {value:?}"),
+                }
+            }
+            _ => anyhow::bail!("unknown GPU option {key}"),
+        }
+        Ok(())
+    }
+
+    pub fn instance(&self) -> wgpu::Instance {
+        wgpu::Instance::new(wgpu::InstanceDescriptor {
+            backends: self.backends,
+            flags: if self.validation {
+                wgpu::InstanceFlags::VALIDATION
+            } else {
+                wgpu::InstanceFlags::empty()
+            },
+            ..Default::default()
+        })
+    }
+
+    /// The selected adapter if one was given, otherwise the one wgpu prefers,
+    /// able to present to `surface` when rendering to a window.
+    pub async fn adapter(
+        &self,
+        instance: &wgpu::Instance,
+        surface: Option<&wgpu::Surface<'_>>,
+    ) -> anyhow::Result<wgpu::Adapter> {
+        let Some(selector) = &self.adapter else {
+            return instance
+                .request_adapter(&wgpu::RequestAdapterOptions {
+                    power_preference: self.power_preference,
+                    force_fallback_adapter: self.software,
+                    compatible_surface: surface,
+                })
+                .await
+                .context(if surface.is_some() {
+                    "No adapter can present to the window"
+                } else {
+                    "No adapter found"
+                });
+        };
+
+        let adapter = instance
+            .enumerate_adapters(self.backends)
+            .into_iter()
+            .enumerate()
+            .find(|(index, adapter)| {
+                let info = adapter.get_info();
+                match selector {
+                    AdapterSelector::Index(i) => i == index,
+                    AdapterSelector::Name(name) => info.name.to_lowercase().contains(name),
+                }
+            })
+            .map(|(_, adapter)| adapter)
+            .with_context(|| match selector {
+                AdapterSelector::Index(index) => format!("No adapter number {index}"),
+                AdapterSelector::Name(name) => format!("No adapter named like {name:?}"),
+            })?;
+
+        let info = adapter.get_info();
+        anyhow::ensure!(
+            !self.software || info.device_type == wgpu::DeviceType::Cpu,
+            "{} is not a software adapter",
+            info.name
+        );
+        anyhow::ensure!(
+            surface.is_none_or(|surface| adapter.is_surface_supported(surface)),
+            "{} can't present to the window",
+            info.name
+        );
+
+        Ok(adapter)
+    }
+}
+
+/// One line summary of an adapter.
+pub fn describe(info: &wgpu::AdapterInfo) -> String {
+    let driver = format!("{} {}", info.driver, info.driver_info);
+    format!(
+        "{} ({:?} on {:?}), driver {}",
+        info.name,
+        info.device_type,
+        info.backend,
+        driver.trim()
+    )
+}
+
+/// Prints the adapters of the selected backends, numbered for `--adapter`.
+pub fn print_list(options: &GpuOptions) {
+    let adapters = options.instance().enumerate_adapters(options.backends);
+    if adapters.is_empty() {
+        println!("No adapters found for {:?}.", options.backends);
+    }
+
+    for (index, adapter) in adapters.iter().enumerate() {
+        let info = adapter.get_info();
+        println!("{index}: {}", describe(&info));
+        println!(
+            "   vendor {:#06x}, device {:#06x}",
+            info.vendor, info.device
+        );
+    }
+}
+
+/// Lists the backends this build supports and the adapters every one of them
+/// exposes, for diagnosing why no adapter could be used with `options`.
+pub fn report(options: &GpuOptions) -> String {
+    let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+        backends: wgpu::Backends::all(),
+        ..Default::default()
+    });
+
+    let mut report = String::new();
+    _ = writeln!(
+        report,
+        "Backends requested: {:?}{}, built in: {:?}",
+        options.backends,
+        if options.software { " (software)" } else { "" },
+        wgpu::Instance::enabled_backend_features()
+    );
+
+    let adapters = instance.enumerate_adapters(wgpu::Backends::all());
+    if adapters.is_empty() {
+        report.push_str("No adapters found on any backend.");
+    }
+    for adapter in &adapters {
+        _ = writeln!(report, "  - {}", describe(&adapter.get_info()));
+    }
+
+    report.trim_end().to_string()
+}
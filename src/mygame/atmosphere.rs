@@ -0,0 +1,90 @@
+use bytemuck::{Pod, Zeroable};
+use cgmath::{InnerSpace, Vector3};
+
+use super::layout::shader_layout;
+
+/// Scattering medium rendered by `scatter.wgsl` and the sun lighting it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Atmosphere {
+    /// Direction towards the sun, normalised on upload.
+    pub sun_direction: Vector3<f32>,
+    pub sun_intensity: f32,
+    /// Wavelengths in micrometres the red, green and blue channels scatter at.
+    pub wavelengths: [f32; 3],
+    /// Scale of the `1 / λ^4` Rayleigh coefficient.
+    pub rayleigh_intensity: f32,
+    /// Height over which the density falls off by `1 / e`, relative to the medium radius.
+    pub density_falloff: f32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod, Zeroable)]
+pub struct AtmosphereUniform {
+    sun_direction: [f32; 3],
+    sun_intensity: f32,
+    wavelengths: [f32; 3],
+    rayleigh_intensity: f32,
+    density_falloff: f32,
+    _padding: [f32; 3],
+}
+
+shader_layout!(
+    AtmosphereUniform,
+    "Atmosphere",
+    sun_direction,
+    sun_intensity,
+    wavelengths,
+    rayleigh_intensity,
+    density_falloff
+);
+
+impl Atmosphere {
+    /// Names accepted by [`Self::preset`].
+    pub const PRESETS: [&'static str; 4] = ["default", "sunset", "hazy", "clear"];
+
+    pub fn new() -> Self {
+        Self {
+            sun_direction: Vector3::new(1.0, 1.0, 1.0),
+            sun_intensity: 20.0,
+            wavelengths: [0.7, 0.9, 0.8],
+            rayleigh_intensity: 0.1,
+            density_falloff: 0.35,
+        }
+    }
+
+    pub fn preset(name: &str) -> Option<Self> {
+        let default = Self::new();
+        Some(match name {
+            "default" => default,
+            "sunset" => Self {
+                sun_direction: Vector3::new(1.0, 0.08, 0.3),
+                sun_intensity: 30.0,
+                rayleigh_intensity: 0.15,
+                ..default
+            },
+            "hazy" => Self {
+                sun_intensity: 15.0,
+                rayleigh_intensity: 0.3,
+                density_falloff: 0.6,
+                ..default
+            },
+            "clear" => Self {
+                rayleigh_intensity: 0.05,
+                density_falloff: 0.25,
+                ..default
+            },
+            _ => return None,
+        })
+    }
+
+    pub fn uniform(&self) -> AtmosphereUniform {
+        AtmosphereUniform {
+            sun_direction: self.sun_direction.normalize().into(),
+            sun_intensity: self.sun_intensity,
+            wavelengths: self.wavelengths,
+            rayleigh_intensity: self.rayleigh_intensity,
+            density_falloff: self.density_falloff,
+            _padding: [0.0; 3],
+        }
+    }
+}
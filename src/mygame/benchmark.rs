@@ -0,0 +1,69 @@
+use std::time::{Duration, Instant};
+
+/// Frames skipped before timing starts, while pipelines and caches warm up.
+const WARMUP_FRAMES: u32 = 10;
+
+/// Frame time statistics over a fixed number of frames.
+pub struct Benchmark {
+    frames: u32,
+    warmup: u32,
+    last_frame: Option<Instant>,
+    frame_times: Vec<Duration>,
+}
+
+impl Benchmark {
+    pub fn new(frames: u32) -> Self {
+        Self {
+            frames,
+            warmup: WARMUP_FRAMES,
+            last_frame: None,
+            frame_times: Vec::with_capacity(frames as usize),
+        }
+    }
+
+    /// Frames to render for `frames` of them to be timed.
+    pub fn total_frames(frames: u32) -> u32 {
+        WARMUP_FRAMES + frames
+    }
+
+    /// Records the end of a frame, returning `true` once every frame was timed.
+    pub fn frame(&mut self) -> bool {
+        let now = Instant::now();
+        let last = self.last_frame.replace(now);
+
+        if self.warmup > 0 {
+            self.warmup -= 1;
+            return false;
+        }
+
+        if let Some(last) = last {
+            self.frame_times.push(now - last);
+        }
+        self.frame_times.len() >= self.frames as usize
+    }
+
+    pub fn report(&self) -> String {
+        let mut times = self.frame_times.clone();
+        times.sort_unstable();
+        if times.is_empty() {
+            return "No frames timed.".to_string();
+        }
+
+        let ms = |time: Duration| time.as_secs_f64() * 1000.0;
+        let percentile = |p: f64| times[((times.len() - 1) as f64 * p).round() as usize];
+        let total: Duration = times.iter().sum();
+        let average = total / times.len() as u32;
+
+        format!(
+            "{} frames in {:.2} s: {:.1} FPS, average {:.3} ms, min {:.3} ms, median {:.3} ms, 99th percentile {:.3} ms, max {:.3} ms",
+            times.len(),
+            total.as_secs_f64(),
+            times.len() as f64 / total.as_secs_f64(),
+            ms(average),
+            ms(times[0]),
+            ms(percentile(0.5)),
+            ms(percentile(0.99)),
+            ms(times[times.len() - 1]),
+        )
+    }
+}
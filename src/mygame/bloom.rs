@@ -0,0 +1,298 @@
+use bytemuck::{Pod, Zeroable};
+
+use super::{
+    layout::shader_layout,
+    pipeline::{BindGroupLayouts, Binding, Blend, PipelineFactory},
+    shader::Shader,
+    texture::Texture,
+    uniform::UniformBuffer,
+};
+
+/// Upper bound on the number of mips in the bloom chain.
+const MAX_MIP_LEVELS: u32 = 6;
+
+pub struct BloomSettings {
+    pub enabled: bool,
+    /// Brightness above which pixels contribute to bloom. 0 keeps bloom energy-conserving.
+    pub threshold: f32,
+    /// Width of the soft transition around the threshold.
+    pub knee: f32,
+    /// Fraction of the final image taken from the bloom chain.
+    pub intensity: f32,
+    /// How much each coarser mip contributes when upsampling, controls the spread.
+    pub radius: f32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod, Zeroable)]
+pub struct BloomUniform {
+    threshold: f32,
+    knee: f32,
+    intensity: f32,
+    _padding: f32,
+}
+
+shader_layout!(BloomUniform, "Bloom", threshold, knee, intensity);
+
+impl BloomSettings {
+    pub fn new() -> Self {
+        Self {
+            enabled: true,
+            threshold: 0.0,
+            knee: 0.5,
+            intensity: 0.04,
+            radius: 0.7,
+        }
+    }
+
+    pub fn uniform(&self) -> BloomUniform {
+        BloomUniform {
+            threshold: self.threshold,
+            knee: self.knee,
+            intensity: if self.enabled { self.intensity } else { 0.0 },
+            _padding: 0.0,
+        }
+    }
+}
+
+/// Bloom over an HDR texture using a downsample/upsample mip pyramid.
+///
+/// The result ends up in [`Bloom::output`] at half the source resolution,
+/// to be blended with the source as `mix(source, bloom, intensity)`.
+pub struct Bloom {
+    pub settings: BloomSettings,
+    uniform_buffer: UniformBuffer<BloomUniform>,
+    layouts: BindGroupLayouts,
+
+    prefilter_pipeline: wgpu::RenderPipeline,
+    downsample_pipeline: wgpu::RenderPipeline,
+    upsample_pipeline: wgpu::RenderPipeline,
+
+    mips: Texture,
+    mip_views: Vec<wgpu::TextureView>,
+    /// Bind group sampling the source, followed by one per mip level.
+    bind_groups: Vec<wgpu::BindGroup>,
+}
+
+impl Bloom {
+    pub fn new(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        source: &Texture,
+        format: wgpu::TextureFormat,
+        shader: &Shader,
+        cache: Option<&wgpu::PipelineCache>,
+    ) -> anyhow::Result<Self> {
+        let settings = BloomSettings::new();
+
+        let uniform_buffer = UniformBuffer::new(device, "bloom", settings.uniform());
+
+        let mut layouts = BindGroupLayouts::default();
+        layouts.derive(device, "bloom", &[(&shader.reflection, 0)])?;
+
+        let (prefilter_pipeline, downsample_pipeline, upsample_pipeline) =
+            Self::create_pipelines(device, &layouts, shader, format, cache)?;
+
+        let (mips, mip_views, bind_groups) =
+            Self::create_mips(device, config, source, format, &layouts, &uniform_buffer)?;
+
+        Ok(Self {
+            settings,
+            uniform_buffer,
+            layouts,
+            prefilter_pipeline,
+            downsample_pipeline,
+            upsample_pipeline,
+            mips,
+            mip_views,
+            bind_groups,
+        })
+    }
+
+    fn create_pipelines(
+        device: &wgpu::Device,
+        layouts: &BindGroupLayouts,
+        shader: &Shader,
+        format: wgpu::TextureFormat,
+        cache: Option<&wgpu::PipelineCache>,
+    ) -> anyhow::Result<(
+        wgpu::RenderPipeline,
+        wgpu::RenderPipeline,
+        wgpu::RenderPipeline,
+    )> {
+        let factory = PipelineFactory {
+            device,
+            layouts,
+            surface_format: format,
+            cache,
+        };
+        let create_pipeline = |label, entry_point, blend| {
+            factory
+                .render(label, shader)
+                .entry_points("vs_main", Some(entry_point))
+                .target(format, blend)
+                .cull_mode(None)
+                .no_depth()
+                .build()
+        };
+
+        let prefilter_pipeline =
+            create_pipeline("bloom_prefilter_pipeline", "fs_prefilter", Blend::Replace)?;
+        let downsample_pipeline =
+            create_pipeline("bloom_downsample_pipeline", "fs_downsample", Blend::Replace)?;
+        // dst = upsampled * radius + dst * (1 - radius), weights across mips sum to one
+        let upsample_pipeline =
+            create_pipeline("bloom_upsample_pipeline", "fs_upsample", Blend::Constant)?;
+
+        Ok((prefilter_pipeline, downsample_pipeline, upsample_pipeline))
+    }
+
+    /// Rebuilds the pipelines from a new shader, keeping the old ones on error.
+    pub fn reload(
+        &mut self,
+        device: &wgpu::Device,
+        shader: &Shader,
+        cache: Option<&wgpu::PipelineCache>,
+    ) -> anyhow::Result<()> {
+        let format = self.mips.texture.format();
+        (
+            self.prefilter_pipeline,
+            self.downsample_pipeline,
+            self.upsample_pipeline,
+        ) = Self::create_pipelines(device, &self.layouts, shader, format, cache)?;
+
+        Ok(())
+    }
+
+    fn create_mips(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        source: &Texture,
+        format: wgpu::TextureFormat,
+        layouts: &BindGroupLayouts,
+        uniform_buffer: &UniformBuffer<BloomUniform>,
+    ) -> anyhow::Result<(Texture, Vec<wgpu::TextureView>, Vec<wgpu::BindGroup>)> {
+        let size = ((config.width / 2).max(1), (config.height / 2).max(1));
+        let mip_level_count =
+            (u32::BITS - size.0.min(size.1).leading_zeros()).clamp(1, MAX_MIP_LEVELS);
+
+        let mips =
+            Texture::create_mip_chain(device, size, format, mip_level_count, Some("bloom_texture"));
+        let mip_views: Vec<_> = (0..mip_level_count).map(|i| mips.mip_view(i)).collect();
+
+        let create_bind_group = |(texture, view): (&wgpu::Texture, &wgpu::TextureView)| {
+            layouts.create_bind_group(
+                device,
+                "bloom",
+                &[
+                    Binding::texture(0, texture, view),
+                    Binding::sampler(1, &mips.sampler),
+                    uniform_buffer.entry(2),
+                ],
+            )
+        };
+
+        let bind_groups = std::iter::once((&source.texture, &source.view))
+            .chain(mip_views.iter().map(|view| (&mips.texture, view)))
+            .map(create_bind_group)
+            .collect::<anyhow::Result<_>>()?;
+
+        Ok((mips, mip_views, bind_groups))
+    }
+
+    /// Recreates the mip chain for a new surface size or source texture.
+    pub fn resize(
+        &mut self,
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        source: &Texture,
+    ) -> anyhow::Result<()> {
+        (self.mips, self.mip_views, self.bind_groups) = Self::create_mips(
+            device,
+            config,
+            source,
+            self.mips.texture.format(),
+            &self.layouts,
+            &self.uniform_buffer,
+        )?;
+        Ok(())
+    }
+
+    pub fn update_settings(&mut self, queue: &wgpu::Queue) {
+        self.uniform_buffer.set(self.settings.uniform());
+        self.uniform_buffer.flush(queue);
+    }
+
+    /// Texture and view of the final bloom result.
+    pub fn output(&self) -> (&wgpu::Texture, &wgpu::TextureView) {
+        (&self.mips.texture, &self.mip_views[0])
+    }
+
+    pub fn uniform_buffer(&self) -> &wgpu::Buffer {
+        self.uniform_buffer.buffer()
+    }
+
+    pub fn render(&self, encoder: &mut wgpu::CommandEncoder) {
+        if !self.settings.enabled {
+            return;
+        }
+
+        // bind_groups[i] samples the source of the pass writing mip i
+        for (i, target) in self.mip_views.iter().enumerate() {
+            let pipeline = if i == 0 {
+                &self.prefilter_pipeline
+            } else {
+                &self.downsample_pipeline
+            };
+            self.pass(encoder, "bloom_downsample_pass", target, pipeline, i, None);
+        }
+
+        for i in (0..self.mip_views.len() - 1).rev() {
+            self.pass(
+                encoder,
+                "bloom_upsample_pass",
+                &self.mip_views[i],
+                &self.upsample_pipeline,
+                i + 2,
+                Some(self.settings.radius as f64),
+            );
+        }
+    }
+
+    fn pass(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        label: &str,
+        target: &wgpu::TextureView,
+        pipeline: &wgpu::RenderPipeline,
+        bind_group: usize,
+        blend_constant: Option<f64>,
+    ) {
+        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some(label),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: target,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+
+        pass.set_pipeline(pipeline);
+        if let Some(c) = blend_constant {
+            pass.set_blend_constant(wgpu::Color {
+                r: c,
+                g: c,
+                b: c,
+                a: c,
+            });
+        }
+        pass.set_bind_group(0, &self.bind_groups[bind_group], &[]);
+        pass.draw(0..3, 0..1);
+    }
+}
@@ -1,10 +1,13 @@
 use bytemuck::{Pod, Zeroable};
-use cgmath::{InnerSpace, Matrix3, Matrix4, Point3, SquareMatrix, Vector3};
+use cgmath::{Deg, InnerSpace, Matrix3, Matrix4, Point3, Rad, SquareMatrix, Vector3, Vector4};
 use winit::{
     event::{DeviceEvent, KeyEvent, WindowEvent},
     keyboard::KeyCode,
 };
 
+use super::layout::shader_layout;
+
+#[derive(Clone)]
 pub struct Camera {
     eye: Point3<f32>,
     direction: Vector3<f32>,
@@ -31,10 +34,72 @@ impl Camera {
         cgmath::Matrix4::look_to_lh(self.eye, self.direction, self.up())
     }
 
-    pub fn uniform(&self) -> CameraUniform {
+    /// Camera `alpha` of the way from this one to `next`.
+    pub fn interpolate(&self, next: &Camera, alpha: f32) -> Camera {
+        Camera {
+            eye: self.eye + (next.eye - self.eye) * alpha,
+            direction: (self.direction + (next.direction - self.direction) * alpha).normalize(),
+        }
+    }
+
+    /// Perspective projection matching the rays scatter.wgsl marches: a 90° vertical
+    /// field of view looking along +z, with depth going from 0 at `NEAR` to 1 at `FAR`.
+    pub fn projection(aspect: f32) -> Matrix4<f32> {
+        const NEAR: f32 = 0.1;
+        const FAR: f32 = 1000.0;
+        let depth = FAR / (FAR - NEAR);
+
+        Matrix4::from_cols(
+            Vector4::new(1.0 / aspect, 0.0, 0.0, 0.0),
+            Vector4::new(0.0, 1.0, 0.0, 0.0),
+            Vector4::new(0.0, 0.0, depth, 1.0),
+            Vector4::new(0.0, 0.0, -NEAR * depth, 0.0),
+        )
+    }
+
+    /// Uniform for a target `aspect` times wider than it's high.
+    pub fn uniform(&self, aspect: f32) -> CameraUniform {
+        let view = self.view();
         CameraUniform {
-            view: self.view().into(),
-            inverse_view: self.view().invert().unwrap().into(),
+            view: view.into(),
+            inverse_view: view.invert().unwrap().into(),
+            view_proj: (Self::projection(aspect) * view).into(),
+        }
+    }
+}
+
+/// Position and orientation to start the camera at.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CameraPose {
+    pub eye: Point3<f32>,
+    /// Rotation around the vertical axis, zero looking along +z.
+    pub yaw: Deg<f32>,
+    /// Rotation around the horizontal axis, positive looking down.
+    pub pitch: Deg<f32>,
+}
+
+impl CameraPose {
+    /// Parses `x,y,z` or `x,y,z,yaw,pitch` with the angles in degrees, the
+    /// pitch strictly between -90 and 90 as the camera can't look straight up
+    /// or down.
+    pub fn parse(value: &str) -> Option<Self> {
+        let values = value
+            .split(',')
+            .map(|v| v.trim().parse::<f32>().ok().filter(|v| v.is_finite()))
+            .collect::<Option<Vec<_>>>()?;
+
+        match values[..] {
+            [x, y, z] => Some(Self {
+                eye: Point3::new(x, y, z),
+                yaw: Deg(0.0),
+                pitch: Deg(0.0),
+            }),
+            [x, y, z, yaw, pitch] if pitch.abs() < 90.0 => Some(Self {
+                eye: Point3::new(x, y, z),
+                yaw: Deg(yaw),
+                pitch: Deg(pitch),
+            }),
+            _ => None,
         }
     }
 }
@@ -44,8 +109,11 @@ impl Camera {
 pub struct CameraUniform {
     view: [[f32; 4]; 4],
     inverse_view: [[f32; 4]; 4],
+    view_proj: [[f32; 4]; 4],
 }
 
+shader_layout!(CameraUniform, "Camera", view, inverse_view, view_proj);
+
 pub struct Axis {
     negative_pressed: bool,
     negative_button: KeyCode,
@@ -72,8 +140,9 @@ impl Axis {
     }
 
     pub fn get(&self) -> f32 {
-        return if self.negative_pressed { -1.0 } else { 0.0 }
-            + if self.positive_pressed { 1.0 } else { 0.0 };
+        let negative = if self.negative_pressed { -1.0 } else { 0.0 };
+        let positive = if self.positive_pressed { 1.0 } else { 0.0 };
+        negative + positive
     }
 }
 
@@ -102,33 +171,44 @@ impl CameraController {
                 self.horizontal.process(event);
                 self.vertical.process(event);
             }
-            WindowEvent::MouseWheel { delta, .. } => {
-                match delta {
-                    winit::event::MouseScrollDelta::LineDelta(_, y) => {
-                        self.speed *= 1.0 + y * 0.1;
-                    }
-                    _ => {}
-                }
+            WindowEvent::MouseWheel {
+                delta: winit::event::MouseScrollDelta::LineDelta(_, y),
+                ..
+            } => {
+                self.speed *= 1.0 + y * 0.1;
             }
             _ => {}
         }
     }
 
     pub fn process_device_events(&mut self, device_event: &DeviceEvent) {
-        match device_event {
-            DeviceEvent::MouseMotion { delta } => {
-                self.camera_motion.0 += delta.0 as f32;
-                self.camera_motion.1 += delta.1 as f32;
-            }
-            _ => {}
+        if let DeviceEvent::MouseMotion { delta } = device_event {
+            self.camera_motion.0 += delta.0 as f32;
+            self.camera_motion.1 += delta.1 as f32;
         }
     }
 
-    pub fn update(&mut self, camera: &mut Camera, delta: f32) {
-        let roty = Matrix3::from_angle_y(cgmath::Rad(self.camera_motion.0 * self.sensitivity));
-        let rotx = Matrix3::from_angle_x(cgmath::Rad(self.camera_motion.1 * self.sensitivity));
+    /// Direction the accumulated mouse motion points the camera in.
+    fn direction(&self) -> Vector3<f32> {
+        let roty = Matrix3::from_angle_y(Rad(self.camera_motion.0 * self.sensitivity));
+        let rotx = Matrix3::from_angle_x(Rad(self.camera_motion.1 * self.sensitivity));
+
+        roty * rotx * Vector3::unit_z()
+    }
 
-        camera.direction = roty * rotx * Vector3::unit_z();
+    /// Moves the camera to `pose`, treating its angles as if reached by mouse motion
+    /// so that looking around continues from there.
+    pub fn set_pose(&mut self, camera: &mut Camera, pose: &CameraPose) {
+        self.camera_motion = (
+            Rad::from(pose.yaw).0 / self.sensitivity,
+            Rad::from(pose.pitch).0 / self.sensitivity,
+        );
+        camera.eye = pose.eye;
+        camera.direction = self.direction();
+    }
+
+    pub fn update(&mut self, camera: &mut Camera, delta: f32) {
+        camera.direction = self.direction();
 
         let movement = (self.horizontal.get() * camera.right()
             + self.vertical.get() * camera.direction)
@@ -141,3 +221,44 @@ impl CameraController {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_position() {
+        let pose = CameraPose::parse("1, 2.5,-30").unwrap();
+        assert_eq!(pose.eye, Point3::new(1.0, 2.5, -30.0));
+        assert_eq!(pose.yaw, Deg(0.0));
+        assert_eq!(pose.pitch, Deg(0.0));
+    }
+
+    #[test]
+    fn parses_position_and_angles() {
+        let pose = CameraPose::parse("0,0,-30,45,-89.5").unwrap();
+        assert_eq!(pose.eye, Point3::new(0.0, 0.0, -30.0));
+        assert_eq!(pose.yaw, Deg(45.0));
+        assert_eq!(pose.pitch, Deg(-89.5));
+    }
+
+    #[test]
+    fn rejects_invalid_poses() {
+        for value in [
+            "",
+            "1,2",
+            "1,2,3,4",
+            "1,2,3,4,5,6",
+            "1,2,z",
+            "0,0,0,0,90",
+            "0,0,0,0,-90",
+            "0,0,0,0,120",
+            "nan,0,0",
+            "0,inf,0",
+            "0,0,0,-inf,0",
+            "0,0,0,0,NaN",
+        ] {
+            assert!(CameraPose::parse(value).is_none(), "{value:?} was accepted");
+        }
+    }
+}
@@ -0,0 +1,116 @@
+use std::path::PathBuf;
+
+use anyhow::Context;
+
+/// Settings stored as `key = value` lines, with `#` starting a comment.
+///
+/// Read from `SCATTER_CONFIG`, or `scatter/scatter.cfg` in the user config
+/// directory. Values changed at runtime are written back on exit, leaving
+/// comments and the order of existing lines untouched.
+#[derive(Clone, Debug, Default)]
+pub struct Config {
+    /// `None` if there is nowhere to store settings, which are then not saved.
+    path: Option<PathBuf>,
+    lines: Vec<String>,
+    changed: bool,
+}
+
+impl Config {
+    pub fn load() -> Self {
+        let Some(path) = config_path() else {
+            log::info!("No user config directory, settings won't be saved.");
+            return Self::default();
+        };
+
+        let lines = match std::fs::read_to_string(&path) {
+            Ok(contents) => {
+                log::info!("Loaded settings from {}.", path.display());
+                contents.lines().map(str::to_string).collect()
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => {
+                log::warn!("Failed to read {}: {e}", path.display());
+                Vec::new()
+            }
+        };
+
+        Self {
+            path: Some(path),
+            lines,
+            changed: false,
+        }
+    }
+
+    /// Key and value of a line, if it holds a setting.
+    fn entry(line: &str) -> Option<(&str, &str)> {
+        let line = line.split_once('#').map_or(line, |(setting, _)| setting);
+        let (key, value) = line.split_once('=')?;
+        Some((key.trim(), value.trim()))
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.lines
+            .iter()
+            .filter_map(|line| Self::entry(line))
+            .find(|(k, _)| *k == key)
+            .map(|(_, value)| value)
+    }
+
+    /// Parses a setting, warning about values `parse` rejects.
+    pub fn parse<T>(&self, key: &str, parse: impl FnOnce(&str) -> Option<T>) -> Option<T> {
+        let value = self.get(key)?;
+        let parsed = parse(value);
+        if parsed.is_none() {
+            log::warn!("Ignoring invalid setting {key} = {value}.");
+        }
+        parsed
+    }
+
+    pub fn set(&mut self, key: &str, value: impl std::fmt::Display) {
+        let value = value.to_string();
+        if self.get(key) == Some(value.as_str()) {
+            return;
+        }
+
+        let line = format!("{key} = {value}");
+        match self
+            .lines
+            .iter_mut()
+            .find(|l| Self::entry(l).is_some_and(|(k, _)| k == key))
+        {
+            Some(existing) => *existing = line,
+            None => self.lines.push(line),
+        }
+        self.changed = true;
+    }
+
+    /// Writes the file if a setting changed since it was loaded.
+    pub fn save(&mut self) -> anyhow::Result<()> {
+        let Some(path) = self.path.as_ref().filter(|_| self.changed) else {
+            return Ok(());
+        };
+
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)
+                .with_context(|| format!("Failed to create {}", dir.display()))?;
+        }
+        let mut contents = self.lines.join("\n");
+        contents.push('\n');
+        std::fs::write(path, contents)
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+
+        self.changed = false;
+        Ok(())
+    }
+}
+
+fn config_path() -> Option<PathBuf> {
+    let var = |name| std::env::var_os(name).map(PathBuf::from);
+
+    var("SCATTER_CONFIG").or_else(|| {
+        var("XDG_CONFIG_HOME")
+            .or_else(|| var("HOME").map(|home| home.join(".config")))
+            .or_else(|| var("APPDATA"))
+            .map(|dir| dir.join("scatter").join("scatter.cfg"))
+    })
+}
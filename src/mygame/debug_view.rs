@@ -0,0 +1,243 @@
+use std::sync::Arc;
+
+use anyhow::Context;
+use winit::{
+    dpi::PhysicalSize,
+    event_loop::ActiveEventLoop,
+    window::{Window, WindowId},
+};
+
+use super::{
+    pipeline::{BindGroupLayouts, Binding, PipelineFactory},
+    present,
+    shader::ShaderLibrary,
+};
+use crate::window::WindowConfig;
+
+/// Render target shown by the [`DebugView`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DebugSource {
+    /// Accumulated scene before post processing.
+    Hdr,
+    /// Output of the bloom upsample chain.
+    Bloom,
+}
+
+impl DebugSource {
+    pub fn name(self) -> &'static str {
+        match self {
+            DebugSource::Hdr => "HDR scene",
+            DebugSource::Bloom => "bloom",
+        }
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            DebugSource::Hdr => DebugSource::Bloom,
+            DebugSource::Bloom => DebugSource::Hdr,
+        }
+    }
+}
+
+/// Secondary window showing an intermediate render target, drawn with the
+/// game's device on a surface of its own.
+pub struct DebugView<'s> {
+    window: Arc<Window>,
+    surface: wgpu::Surface<'s>,
+    config: wgpu::SurfaceConfiguration,
+    layouts: BindGroupLayouts,
+    pipeline: wgpu::RenderPipeline,
+    sampler: wgpu::Sampler,
+    pub source: DebugSource,
+}
+
+impl DebugView<'_> {
+    pub fn open(
+        event_loop: &ActiveEventLoop,
+        instance: &wgpu::Instance,
+        adapter: &wgpu::Adapter,
+        device: &wgpu::Device,
+        shaders: &ShaderLibrary,
+        cache: Option<&wgpu::PipelineCache>,
+    ) -> anyhow::Result<Self> {
+        let window = WindowConfig {
+            title: "Scatter debug view".to_string(),
+            size: Some(PhysicalSize::new(640, 360)),
+            ..Default::default()
+        }
+        .open(event_loop)?;
+
+        let surface = instance
+            .create_surface(window.clone())
+            .context("Failed to create the debug view surface")?;
+
+        let surface_caps = surface.get_capabilities(adapter);
+        let format = surface_caps
+            .formats
+            .iter()
+            .copied()
+            .find(|f| f.is_srgb())
+            .or(surface_caps.formats.first().copied())
+            .context("The adapter can't present to the debug view")?;
+
+        let size = window.inner_size();
+        let config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format,
+            width: size.width.max(1),
+            height: size.height.max(1),
+            // Waiting for vertical blank here as well would halve the frame rate
+            present_mode: present::supported(
+                wgpu::PresentMode::Mailbox,
+                &surface_caps.present_modes,
+            ),
+            desired_maximum_frame_latency: 2,
+            alpha_mode: wgpu::CompositeAlphaMode::Opaque,
+            view_formats: vec![],
+        };
+        surface.configure(device, &config);
+
+        let mut layouts = BindGroupLayouts::default();
+        let pipeline = Self::create_pipeline(device, &mut layouts, shaders, format, cache)?;
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("debug_view_sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        Ok(Self {
+            window,
+            surface,
+            config,
+            layouts,
+            pipeline,
+            sampler,
+            source: DebugSource::Hdr,
+        })
+    }
+
+    fn create_pipeline(
+        device: &wgpu::Device,
+        layouts: &mut BindGroupLayouts,
+        shaders: &ShaderLibrary,
+        format: wgpu::TextureFormat,
+        cache: Option<&wgpu::PipelineCache>,
+    ) -> anyhow::Result<wgpu::RenderPipeline> {
+        let defines: &[(&str, &str)] = if format.is_srgb() {
+            &[]
+        } else {
+            &[("ENCODE_SRGB", "")]
+        };
+        let shader = shaders.load_with(device, "debug_view.wgsl", defines)?;
+        layouts.derive(device, "debug_view", &[(&shader.reflection, 0)])?;
+
+        PipelineFactory {
+            device,
+            layouts,
+            surface_format: format,
+            cache,
+        }
+        .render("debug_view_pipeline", &shader)
+        .cull_mode(None)
+        .no_depth()
+        .build()
+    }
+
+    /// Rebuilds the pipeline after `debug_view.wgsl` changed, keeping the old one on error.
+    pub fn reload(
+        &mut self,
+        device: &wgpu::Device,
+        shaders: &ShaderLibrary,
+        cache: Option<&wgpu::PipelineCache>,
+    ) -> anyhow::Result<()> {
+        let mut layouts = BindGroupLayouts::default();
+        self.pipeline =
+            Self::create_pipeline(device, &mut layouts, shaders, self.config.format, cache)?;
+        self.layouts = layouts;
+        Ok(())
+    }
+
+    pub fn id(&self) -> WindowId {
+        self.window.id()
+    }
+
+    pub fn request_redraw(&self) {
+        self.window.request_redraw();
+    }
+
+    /// Reconfigures the surface, ignoring the zero size of a minimised window.
+    pub fn resize(&mut self, device: &wgpu::Device, new_size: PhysicalSize<u32>) {
+        if new_size.width == 0 || new_size.height == 0 {
+            return;
+        }
+
+        self.config.width = new_size.width;
+        self.config.height = new_size.height;
+        self.surface.configure(device, &self.config);
+    }
+
+    /// Draws `view` of `texture` stretched over the window. The bind group is
+    /// created every time since the targets are recreated whenever the main
+    /// window resizes.
+    pub fn render(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        texture: &wgpu::Texture,
+        view: &wgpu::TextureView,
+    ) -> anyhow::Result<()> {
+        let image = match self.surface.get_current_texture() {
+            Ok(image) => image,
+            Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) => {
+                self.surface.configure(device, &self.config);
+                return Ok(());
+            }
+            Err(e) => return Err(e).context("Failed to acquire the debug view surface"),
+        };
+
+        let bind_group = self.layouts.create_bind_group(
+            device,
+            "debug_view",
+            &[
+                Binding::texture(0, texture, view),
+                Binding::sampler(1, &self.sampler),
+            ],
+        )?;
+
+        let output = image
+            .texture
+            .create_view(&wgpu::TextureViewDescriptor::default());
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("debug_view_encoder"),
+        });
+
+        {
+            let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("debug_view_pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &output,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+
+            pass.set_pipeline(&self.pipeline);
+            pass.set_bind_group(0, &bind_group, &[]);
+            pass.draw(0..3, 0..1);
+        }
+
+        queue.submit(std::iter::once(encoder.finish()));
+        image.present();
+
+        Ok(())
+    }
+}
@@ -0,0 +1,182 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+use bytemuck::{Pod, Zeroable};
+
+use super::layout::shader_layout;
+
+/// Number of bins in the luminance histogram, matches `HISTOGRAM_BINS` in `exposure.wgsl`.
+pub const HISTOGRAM_BINS: u64 = 256;
+/// Workgroup size of the histogram pass in each dimension.
+pub const HISTOGRAM_WORKGROUP_SIZE: u32 = 16;
+
+// Each invocation of a histogram workgroup clears and flushes the bin at its
+// local index, which only covers every bin with one invocation per bin.
+const _: () = assert!(HISTOGRAM_WORKGROUP_SIZE * HISTOGRAM_WORKGROUP_SIZE == HISTOGRAM_BINS as u32);
+
+/// Settings for automatic exposure. Luminance is measured in a histogram built
+/// over the HDR scene and the exposure adapts towards `key / average` over time.
+pub struct AutoExposure {
+    pub enabled: bool,
+    /// Lower bound of the histogram in log2 luminance.
+    pub min_log_luminance: f32,
+    /// Upper bound of the histogram in log2 luminance.
+    pub max_log_luminance: f32,
+    /// Fraction of darkest pixels ignored when averaging.
+    pub low_percentile: f32,
+    /// Fraction of pixels below which the brightest ones are ignored.
+    pub high_percentile: f32,
+    /// Adaptation rate when the scene gets brighter.
+    pub speed_up: f32,
+    /// Adaptation rate when the scene gets darker.
+    pub speed_down: f32,
+    /// Target middle grey.
+    pub key: f32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod, Zeroable)]
+pub struct AutoExposureUniform {
+    min_log_luminance: f32,
+    log_luminance_range: f32,
+    low_percentile: f32,
+    high_percentile: f32,
+    speed_up: f32,
+    speed_down: f32,
+    key: f32,
+    enabled: u32,
+}
+
+shader_layout!(
+    AutoExposureUniform,
+    "AutoExposure",
+    min_log_luminance,
+    log_luminance_range,
+    low_percentile,
+    high_percentile,
+    speed_up,
+    speed_down,
+    key,
+    enabled,
+);
+
+/// Exposure computed on the GPU, stored in a storage buffer.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, Pod, Zeroable)]
+pub struct ExposureState {
+    pub average_log_luminance: f32,
+    pub exposure_ev: f32,
+}
+
+shader_layout!(
+    ExposureState,
+    "ExposureState",
+    average_log_luminance,
+    exposure_ev
+);
+
+impl AutoExposure {
+    pub fn new() -> Self {
+        Self {
+            enabled: true,
+            min_log_luminance: -10.0,
+            max_log_luminance: 6.0,
+            low_percentile: 0.5,
+            high_percentile: 0.95,
+            speed_up: 3.0,
+            speed_down: 1.0,
+            key: 0.18,
+        }
+    }
+
+    pub fn uniform(&self) -> AutoExposureUniform {
+        AutoExposureUniform {
+            min_log_luminance: self.min_log_luminance,
+            log_luminance_range: self.max_log_luminance - self.min_log_luminance,
+            low_percentile: self.low_percentile,
+            high_percentile: self.high_percentile,
+            speed_up: self.speed_up,
+            speed_down: self.speed_down,
+            key: self.key,
+            enabled: self.enabled as u32,
+        }
+    }
+}
+
+/// Reads the GPU exposure state back to the CPU for debugging.
+pub struct ExposureReadback {
+    buffer: wgpu::Buffer,
+    pending: bool,
+    mapped: Arc<AtomicBool>,
+    /// Set when mapping failed, so that the next readback can start.
+    failed: Arc<AtomicBool>,
+}
+
+impl ExposureReadback {
+    pub fn new(device: &wgpu::Device) -> Self {
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("exposure_readback"),
+            size: std::mem::size_of::<ExposureState>() as u64,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        Self {
+            buffer,
+            pending: false,
+            mapped: Arc::new(AtomicBool::new(false)),
+            failed: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Starts a readback unless one is in flight. If this returns `true`, [`Self::copy`]
+    /// must be recorded this frame and [`Self::map`] called after submitting.
+    pub fn begin(&mut self) -> bool {
+        if self.pending {
+            return false;
+        }
+
+        self.pending = true;
+        true
+    }
+
+    pub fn copy(&self, encoder: &mut wgpu::CommandEncoder, state: &wgpu::Buffer) {
+        encoder.copy_buffer_to_buffer(state, 0, &self.buffer, 0, self.buffer.size());
+    }
+
+    pub fn map(&self) {
+        let mapped = self.mapped.clone();
+        let failed = self.failed.clone();
+        self.buffer
+            .slice(..)
+            .map_async(wgpu::MapMode::Read, move |result| match result {
+                Ok(()) => mapped.store(true, Ordering::Release),
+                Err(e) => {
+                    log::warn!("Failed to read back the exposure state: {e}");
+                    failed.store(true, Ordering::Release);
+                }
+            });
+    }
+
+    /// Returns the state once the readback has completed.
+    pub fn poll(&mut self) -> Option<ExposureState> {
+        if self.failed.swap(false, Ordering::Acquire) {
+            self.pending = false;
+            return None;
+        }
+        if !self.mapped.swap(false, Ordering::Acquire) {
+            return None;
+        }
+
+        let state = {
+            let data = self.buffer.slice(..).get_mapped_range();
+            *bytemuck::from_bytes::<ExposureState>(&data)
+        };
+        self.buffer.unmap();
+        self.pending = false;
+
+        Some(state)
+    }
+}
@@ -0,0 +1,62 @@
+use std::time::{Duration, Instant};
+
+/// Frame rates the limiter cycles through, `None` being uncapped.
+const PRESETS: &[Option<u32>] = &[None, Some(30), Some(60), Some(120), Some(144), Some(240)];
+
+/// How long before a deadline to stop sleeping and spin instead, since sleeps
+/// can overshoot by around a millisecond or more depending on the platform.
+const SPIN_TIME: Duration = Duration::from_millis(2);
+
+/// Caps the frame rate by waiting out what's left of each frame on the CPU.
+pub struct FrameLimiter {
+    max_fps: Option<u32>,
+    next_frame: Option<Instant>,
+}
+
+impl FrameLimiter {
+    pub fn new(max_fps: Option<u32>) -> Self {
+        Self {
+            max_fps: max_fps.filter(|fps| *fps > 0),
+            next_frame: None,
+        }
+    }
+
+    pub fn max_fps(&self) -> Option<u32> {
+        self.max_fps
+    }
+
+    /// Switches to the next preset frame rate.
+    pub fn cycle(&mut self) {
+        let index = PRESETS.iter().position(|p| *p == self.max_fps);
+        self.max_fps = PRESETS[index.map_or(0, |i| (i + 1) % PRESETS.len())];
+        self.next_frame = None;
+    }
+
+    /// Blocks until the next frame is due.
+    pub fn wait(&mut self) {
+        let Some(max_fps) = self.max_fps else {
+            return;
+        };
+
+        let period = Duration::from_secs_f64(1.0 / max_fps as f64);
+        let now = Instant::now();
+        let deadline = self.next_frame.unwrap_or(now);
+
+        if let Some(remaining) = deadline.checked_duration_since(now) {
+            if let Some(sleep) = remaining.checked_sub(SPIN_TIME) {
+                std::thread::sleep(sleep);
+            }
+            while Instant::now() < deadline {
+                std::hint::spin_loop();
+            }
+        }
+
+        // Scheduling from the deadline keeps the average rate exact, unless a
+        // slow frame put us so far behind that catching up would mean bursts
+        self.next_frame = Some(if now > deadline + period {
+            now + period
+        } else {
+            deadline + period
+        });
+    }
+}
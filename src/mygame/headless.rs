@@ -0,0 +1,143 @@
+use anyhow::Context;
+use pollster::FutureExt;
+use winit::dpi::PhysicalSize;
+
+use super::{benchmark::Benchmark, png, scene::Scene, MyGame, Options, HEADLESS_FORMAT};
+use crate::window::{Game, FIXED_TIMESTEP};
+
+/// Size of the images rendered when no `--size` is given.
+const DEFAULT_SIZE: PhysicalSize<u32> = PhysicalSize::new(1280, 720);
+
+/// Rendering without a window, one fixed update per frame.
+#[derive(Clone, Debug)]
+pub struct Headless {
+    /// Defaults to one frame, or as many as the benchmark needs.
+    pub frames: Option<u32>,
+    /// Where images are written. A run of `#` is replaced by the zero padded
+    /// frame number to keep every frame, otherwise only the last one is written.
+    pub output: Option<String>,
+}
+
+impl Headless {
+    fn frame_path(&self, frame: u32, frames: u32) -> Option<String> {
+        let output = self.output.as_ref()?;
+        let Some(start) = output.find('#') else {
+            return (frame + 1 == frames).then(|| output.clone());
+        };
+
+        let width = output[start..].chars().take_while(|&c| c == '#').count();
+        Some(format!(
+            "{}{frame:0width$}{}",
+            &output[..start],
+            &output[start + width..]
+        ))
+    }
+}
+
+/// Renders the requested frames, writing images and timing them as requested.
+pub fn run(options: &Options, headless: &Headless) -> anyhow::Result<()> {
+    let frames = headless
+        .frames
+        .unwrap_or_else(|| options.benchmark.map_or(1, Benchmark::total_frames));
+    let size = options.size.unwrap_or(DEFAULT_SIZE);
+    let scene = Scene::load(options.scene.as_deref())?;
+    let mut game = MyGame::create(None, size, options, scene).block_on()?;
+    let mut benchmark = options.benchmark.map(Benchmark::new);
+
+    let target = game.device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("headless_target"),
+        size: wgpu::Extent3d {
+            width: size.width,
+            height: size.height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: HEADLESS_FORMAT,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+        view_formats: &[],
+    });
+    let view = target.create_view(&wgpu::TextureViewDescriptor::default());
+
+    let row_bytes = size.width * 4;
+    let padded_row_bytes = row_bytes.next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+    let readback = game.device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("headless_readback"),
+        size: padded_row_bytes as u64 * size.height as u64,
+        usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    });
+
+    for frame in 0..frames {
+        game.update(FIXED_TIMESTEP);
+        game.render_frame(1.0, &view);
+
+        if let Some(path) = headless.frame_path(frame, frames) {
+            let mut encoder = game
+                .device
+                .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                    label: Some("headless_readback"),
+                });
+            encoder.copy_texture_to_buffer(
+                target.as_image_copy(),
+                wgpu::ImageCopyBuffer {
+                    buffer: &readback,
+                    layout: wgpu::ImageDataLayout {
+                        offset: 0,
+                        bytes_per_row: Some(padded_row_bytes),
+                        rows_per_image: None,
+                    },
+                },
+                target.size(),
+            );
+            game.queue.submit(std::iter::once(encoder.finish()));
+
+            let pixels = read_pixels(&game.device, &readback, row_bytes, padded_row_bytes)?;
+            png::write(path.as_ref(), size.width, size.height, &pixels)?;
+            log::info!("Wrote {path}.");
+        } else if benchmark.is_some() {
+            // Time the GPU as well rather than how fast commands can be queued
+            game.device.poll(wgpu::Maintain::Wait);
+        }
+
+        if benchmark.as_mut().is_some_and(Benchmark::frame) {
+            break;
+        }
+    }
+
+    if let Some(benchmark) = &benchmark {
+        println!("{}", benchmark.report());
+    }
+
+    Ok(())
+}
+
+/// Maps `buffer` and returns its rows without the alignment padding.
+fn read_pixels(
+    device: &wgpu::Device,
+    buffer: &wgpu::Buffer,
+    row_bytes: u32,
+    padded_row_bytes: u32,
+) -> anyhow::Result<Vec<u8>> {
+    let slice = buffer.slice(..);
+    let (sender, receiver) = std::sync::mpsc::channel();
+    slice.map_async(wgpu::MapMode::Read, move |result| {
+        _ = sender.send(result);
+    });
+    device.poll(wgpu::Maintain::Wait);
+    receiver
+        .recv()
+        .context("The readback buffer was dropped")?
+        .context("Failed to map the readback buffer")?;
+
+    let pixels = slice
+        .get_mapped_range()
+        .chunks_exact(padded_row_bytes as usize)
+        .flat_map(|row| &row[..row_bytes as usize])
+        .copied()
+        .collect();
+    buffer.unmap();
+
+    Ok(pixels)
+}
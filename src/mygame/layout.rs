@@ -0,0 +1,172 @@
+use std::fmt::Write;
+
+/// Offset and size of a struct member in bytes.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FieldLayout {
+    pub name: String,
+    pub offset: u32,
+    pub size: u32,
+}
+
+/// Memory layout of a struct shared between Rust and WGSL.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StructLayout {
+    pub size: u32,
+    pub align: u32,
+    pub fields: Vec<FieldLayout>,
+}
+
+/// A `#[repr(C)]` type uploaded to a shader, implemented through [`shader_layout!`].
+pub trait ShaderLayout {
+    /// Name of the matching struct in WGSL.
+    const WGSL_NAME: &'static str;
+
+    /// Layout of the fields visible to the shader, padding fields are left out.
+    fn layout() -> StructLayout;
+}
+
+/// Implements [`ShaderLayout`] for a struct from its WGSL name and the fields
+/// that have a WGSL counterpart, in declaration order.
+macro_rules! shader_layout {
+    ($ty:ty, $wgsl:literal, $($field:ident),+ $(,)?) => {
+        impl $crate::mygame::layout::ShaderLayout for $ty {
+            const WGSL_NAME: &'static str = $wgsl;
+
+            fn layout() -> $crate::mygame::layout::StructLayout {
+                fn field_size<S, F>(_: impl Fn(&S) -> &F) -> u32 {
+                    std::mem::size_of::<F>() as u32
+                }
+
+                $crate::mygame::layout::StructLayout {
+                    size: std::mem::size_of::<$ty>() as u32,
+                    align: std::mem::align_of::<$ty>() as u32,
+                    fields: vec![$($crate::mygame::layout::FieldLayout {
+                        name: stringify!($field).to_string(),
+                        offset: std::mem::offset_of!($ty, $field) as u32,
+                        size: field_size(|s: &$ty| &s.$field),
+                    }),+],
+                }
+            }
+        }
+    };
+}
+
+pub(crate) use shader_layout;
+
+/// Layout of the struct named `name` in a WGSL module, without padding members.
+fn wgsl_layout(module: &naga::Module, name: &str) -> anyhow::Result<StructLayout> {
+    let mut layouter = naga::proc::Layouter::default();
+    layouter.update(module.to_ctx())?;
+
+    let (handle, members, span) = module
+        .types
+        .iter()
+        .find_map(|(handle, ty)| match &ty.inner {
+            naga::TypeInner::Struct { members, span } if ty.name.as_deref() == Some(name) => {
+                Some((handle, members, *span))
+            }
+            _ => None,
+        })
+        .ok_or_else(|| anyhow::anyhow!("struct {name} is not declared"))?;
+
+    Ok(StructLayout {
+        size: span,
+        align: layouter[handle].alignment.round_up(1),
+        fields: members
+            .iter()
+            .filter(|member| !member.name.as_ref().is_some_and(|n| n.starts_with('_')))
+            .map(|member| FieldLayout {
+                name: member.name.clone().unwrap_or_default(),
+                offset: member.offset,
+                size: layouter[member.ty].size,
+            })
+            .collect(),
+    })
+}
+
+/// Checks that `T` matches its declaration in a WGSL module, returning a
+/// side by side comparison of both layouts if it doesn't.
+pub fn verify<T: ShaderLayout>(module: &naga::Module, shader: &str) -> anyhow::Result<()> {
+    let wgsl = wgsl_layout(module, T::WGSL_NAME).map_err(|e| anyhow::anyhow!("{shader}: {e}"))?;
+    let rust = T::layout();
+
+    // Uniform buffers are bound in multiples of the WGSL alignment
+    let size_matches = rust.size == wgsl.size && rust.size % wgsl.align == 0;
+    let fields_match = rust.fields == wgsl.fields;
+    if size_matches && fields_match {
+        return Ok(());
+    }
+
+    let mut diff = format!(
+        "{} does not match struct {} in {shader}:\n  {:<24}{:<20}{}\n",
+        std::any::type_name::<T>(),
+        T::WGSL_NAME,
+        "",
+        "rust (offset, size)",
+        "wgsl (offset, size)"
+    );
+
+    let count = rust.fields.len().max(wgsl.fields.len());
+    for i in 0..count {
+        let (r, w) = (rust.fields.get(i), wgsl.fields.get(i));
+        let describe = |field: Option<&FieldLayout>| {
+            field.map_or("-".to_string(), |f| format!("{}, {}", f.offset, f.size))
+        };
+        let name = match (r, w) {
+            (Some(r), Some(w)) if r.name != w.name => format!("{} / {}", r.name, w.name),
+            (Some(f), _) | (_, Some(f)) => f.name.clone(),
+            (None, None) => unreachable!(),
+        };
+
+        _ = writeln!(
+            diff,
+            "{} {name:<24}{:<20}{}",
+            if r == w { ' ' } else { '!' },
+            describe(r),
+            describe(w)
+        );
+    }
+
+    let rust_size = format!("{} ({})", rust.size, rust.align);
+    let wgsl_size = format!("{} ({})", wgsl.size, wgsl.align);
+    _ = write!(
+        diff,
+        "{} {:<24}{rust_size:<20}{wgsl_size}",
+        if size_matches { ' ' } else { '!' },
+        "size (align)",
+    );
+
+    Err(anyhow::anyhow!(diff))
+}
+
+#[cfg(test)]
+mod tests {
+    use bytemuck::{Pod, Zeroable};
+
+    use super::*;
+
+    #[repr(C)]
+    #[derive(Clone, Copy, Pod, Zeroable)]
+    struct Swapped {
+        scale: f32,
+        offset: [f32; 3],
+    }
+
+    shader_layout!(Swapped, "Params", scale, offset);
+
+    #[test]
+    fn mismatch_lists_the_differing_fields() {
+        let module = naga::front::wgsl::parse_str(
+            "struct Params { offset: vec3<f32>, scale: f32 };
+            @group(0) @binding(0) var<uniform> params: Params;",
+        )
+        .unwrap();
+
+        let diff = verify::<Swapped>(&module, "params.wgsl")
+            .unwrap_err()
+            .to_string();
+        assert!(diff.contains("does not match struct Params in params.wgsl"));
+        assert!(diff.contains("! scale / offset"), "{diff}");
+        assert!(diff.contains("! offset / scale"), "{diff}");
+    }
+}
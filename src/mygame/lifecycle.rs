@@ -0,0 +1,93 @@
+/// Surface of the main window through suspend and resume: dropped when the app
+/// is suspended and recreated when it resumes, while the device and everything
+/// else stay alive.
+///
+/// Generic over the surface so that the transitions don't need a GPU.
+pub struct SurfaceLifecycle<S> {
+    surface: Option<S>,
+    /// Whether there is a window to present to, headless rendering never has a surface.
+    windowed: bool,
+}
+
+impl<S> SurfaceLifecycle<S> {
+    /// Starts active with `surface`, or headless without one.
+    pub fn new(surface: Option<S>) -> Self {
+        Self {
+            windowed: surface.is_some(),
+            surface,
+        }
+    }
+
+    pub fn get(&self) -> Option<&S> {
+        self.surface.as_ref()
+    }
+
+    /// Whether the surface was dropped and waits to be recreated.
+    pub fn is_suspended(&self) -> bool {
+        self.windowed && self.surface.is_none()
+    }
+
+    /// Drops the surface, returning whether there was one.
+    pub fn suspend(&mut self) -> bool {
+        self.surface.take().is_some()
+    }
+
+    /// Recreates the surface with `create` after a suspension, returning it.
+    /// Does nothing while active or headless. If `create` fails the surface
+    /// stays suspended, so resuming can be tried again.
+    pub fn resume(
+        &mut self,
+        create: impl FnOnce() -> anyhow::Result<S>,
+    ) -> anyhow::Result<Option<&S>> {
+        if !self.is_suspended() {
+            return Ok(None);
+        }
+        Ok(Some(self.surface.insert(create()?)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn suspend_and_resume_cycles() {
+        let mut lifecycle = SurfaceLifecycle::new(Some(0));
+        assert_eq!(lifecycle.get(), Some(&0));
+        assert!(!lifecycle.is_suspended());
+
+        // Resuming while active keeps the surface
+        let resumed = lifecycle.resume(|| panic!("recreated an active surface"));
+        assert_eq!(resumed.unwrap(), None);
+
+        assert!(lifecycle.suspend());
+        assert!(lifecycle.is_suspended());
+        assert_eq!(lifecycle.get(), None);
+
+        assert_eq!(lifecycle.resume(|| Ok(1)).unwrap(), Some(&1));
+        assert!(!lifecycle.is_suspended());
+        assert_eq!(lifecycle.get(), Some(&1));
+
+        assert!(lifecycle.suspend());
+        assert!(!lifecycle.suspend(), "suspended twice");
+        assert!(lifecycle.is_suspended());
+
+        assert!(lifecycle.resume(|| anyhow::bail!("no window")).is_err());
+        assert!(lifecycle.is_suspended());
+
+        assert_eq!(lifecycle.resume(|| Ok(2)).unwrap(), Some(&2));
+        assert!(!lifecycle.is_suspended());
+        assert_eq!(lifecycle.get(), Some(&2));
+    }
+
+    #[test]
+    fn headless_never_creates_a_surface() {
+        let mut lifecycle = SurfaceLifecycle::<u32>::new(None);
+        assert!(!lifecycle.suspend());
+        assert!(!lifecycle.is_suspended());
+
+        let resumed = lifecycle.resume(|| panic!("created a headless surface"));
+        assert_eq!(resumed.unwrap(), None);
+        assert_eq!(lifecycle.get(), None);
+    }
+}
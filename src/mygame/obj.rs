@@ -0,0 +1,197 @@
+use std::{collections::HashMap, path::Path};
+
+use anyhow::Context;
+
+use super::mesh::Vertex;
+
+/// Reads the positions, texture coordinates and faces of a Wavefront OBJ
+/// model, fanning polygons into triangles. Normals, groups and materials are
+/// ignored.
+pub fn load(path: &Path) -> anyhow::Result<(Vec<Vertex>, Vec<u32>)> {
+    let source = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    parse(&source).with_context(|| format!("Invalid model {}", path.display()))
+}
+
+fn parse(source: &str) -> anyhow::Result<(Vec<Vertex>, Vec<u32>)> {
+    let mut positions = Vec::new();
+    let mut uvs = Vec::new();
+    let mut vertices = Vec::new();
+    let mut indices = Vec::new();
+    // Index of the vertex made of a position and texture coordinate
+    let mut unique = HashMap::new();
+
+    for (number, line) in source.lines().enumerate() {
+        let line_error = |message: &str| anyhow::anyhow!("line {}: {message}", number + 1);
+        let line = line.split_once('#').map_or(line, |(data, _)| data);
+        let mut words = line.split_whitespace();
+        let Some(kind) = words.next() else {
+            continue;
+        };
+
+        let numbers = |count: usize| {
+            let values = words
+                .clone()
+                .take(count)
+                .map(str::parse)
+                .collect::<Result<Vec<f32>, _>>()
+                .ok()
+                .filter(|values| values.len() == count);
+            values.ok_or_else(|| line_error(&format!("expected {count} numbers")))
+        };
+
+        match kind {
+            "v" => positions.push(<[f32; 3]>::try_from(numbers(3)?).unwrap()),
+            "vt" => {
+                let [u, v] = <[f32; 2]>::try_from(numbers(2)?).unwrap();
+                // OBJ puts v = 0 at the bottom of the image
+                uvs.push([u, 1.0 - v]);
+            }
+            "f" => {
+                let mut face = Vec::new();
+                for corner in words {
+                    let mut parts = corner.split('/');
+                    let mut index = |count: usize| -> anyhow::Result<Option<usize>> {
+                        let Some(part) = parts.next().filter(|p| !p.is_empty()) else {
+                            return Ok(None);
+                        };
+                        let index: i64 = part
+                            .parse()
+                            .map_err(|_| line_error(&format!("invalid index {part:?}")))?;
+                        // Negative indices count back from the last element so far
+                        let resolved = if index < 0 {
+                            count as i64 + index
+                        } else {
+                            index - 1
+                        };
+                        anyhow::ensure!(
+                            (0..count as i64).contains(&resolved),
+                            line_error(&format!("index {index} out of range"))
+                        );
+                        Ok(Some(resolved as usize))
+                    };
+
+                    let position = index(positions.len())?
+                        .ok_or_else(|| line_error("face corner without a position"))?;
+                    let uv = index(uvs.len())?;
+
+                    let vertex = *unique.entry((position, uv)).or_insert_with(|| {
+                        vertices.push(Vertex {
+                            position: positions[position],
+                            uv: uv.map_or([0.0, 0.0], |uv| uvs[uv]),
+                        });
+                        vertices.len() as u32 - 1
+                    });
+                    face.push(vertex);
+                }
+
+                anyhow::ensure!(face.len() >= 3, line_error("faces need three corners"));
+                for i in 1..face.len() - 1 {
+                    indices.extend([face[0], face[i], face[i + 1]]);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    anyhow::ensure!(!indices.is_empty(), "no faces");
+    Ok((vertices, indices))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn positions(vertices: &[Vertex]) -> Vec<[f32; 3]> {
+        vertices.iter().map(|vertex| vertex.position).collect()
+    }
+
+    fn error(source: &str) -> String {
+        format!("{:#}", parse(source).err().unwrap())
+    }
+
+    #[test]
+    fn fans_polygons() {
+        let (vertices, indices) = parse(
+            "# square
+            v 0 0 0
+            v 1 0 0
+            v 1 1 0
+            v 0 1 0
+            f 1 2 3 4",
+        )
+        .unwrap();
+
+        assert_eq!(vertices.len(), 4);
+        assert_eq!(indices, [0, 1, 2, 0, 2, 3]);
+    }
+
+    #[test]
+    fn shares_vertices() {
+        let (vertices, indices) = parse(
+            "v 0 0 0
+            v 1 0 0
+            v 1 1 0
+            v 0 1 0
+            vt 0 0
+            vt 1 1
+            f 1/1 2/1 3/1
+            f 1/1 3/1 4/2",
+        )
+        .unwrap();
+
+        assert_eq!(indices, [0, 1, 2, 0, 2, 3]);
+        assert_eq!(vertices[0].uv, [0.0, 1.0]);
+        assert_eq!(vertices[3].uv, [1.0, 0.0]);
+    }
+
+    #[test]
+    fn resolves_negative_indices() {
+        let (vertices, indices) = parse(
+            "v 0 0 0
+            v 1 0 0
+            v 1 1 0
+            f -3 -2 -1
+            v 2 2 2
+            f -4//1 -1//1 3//1",
+        )
+        .unwrap();
+
+        assert_eq!(indices, [0, 1, 2, 0, 3, 2]);
+        assert_eq!(positions(&vertices)[3], [2.0, 2.0, 2.0]);
+    }
+
+    #[test]
+    fn ignores_normals_and_comments() {
+        let (vertices, _) = parse(
+            "v 0 0 0 # origin
+            v 1 0 0
+            v 0 1 0
+            vn 0 0 1
+            g triangle
+            f 1//1 2//1 3//1",
+        )
+        .unwrap();
+
+        assert_eq!(
+            positions(&vertices),
+            [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]]
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_lines() {
+        assert_eq!(error("v 0 0"), "line 1: expected 3 numbers");
+        assert_eq!(error("v 0 0 0\nvt 0 x"), "line 2: expected 2 numbers");
+        assert_eq!(error("v 0 0 0\nf 1 a 1"), "line 2: invalid index \"a\"");
+        assert_eq!(error("v 0 0 0\nf 1 1 2"), "line 2: index 2 out of range");
+        assert_eq!(error("v 0 0 0\nf 1 1 -2"), "line 2: index -2 out of range");
+        assert_eq!(error("v 0 0 0\nf 1 1 0"), "line 2: index 0 out of range");
+        assert_eq!(
+            error("v 0 0 0\nf 1 1 /1"),
+            "line 2: face corner without a position"
+        );
+        assert_eq!(error("v 0 0 0\nf 1 1"), "line 2: faces need three corners");
+        assert_eq!(error("v 0 0 0"), "no faces");
+    }
+}
@@ -0,0 +1,531 @@
+use std::collections::{BTreeMap, HashMap};
+
+use super::{
+    reflection::{self, Reflection, ShaderBinding},
+    shader::{checked, Shader},
+    texture::Texture,
+};
+
+/// Resource bound at one binding of a bind group. Texture views are given with
+/// their texture, since a view doesn't tell its format and usages.
+pub struct Binding<'a> {
+    pub binding: u32,
+    pub resource: wgpu::BindingResource<'a>,
+    pub texture: Option<&'a wgpu::Texture>,
+}
+
+impl<'a> Binding<'a> {
+    pub fn buffer(binding: u32, buffer: &'a wgpu::Buffer) -> Self {
+        Self {
+            binding,
+            resource: buffer.as_entire_binding(),
+            texture: None,
+        }
+    }
+
+    pub fn sampler(binding: u32, sampler: &'a wgpu::Sampler) -> Self {
+        Self {
+            binding,
+            resource: wgpu::BindingResource::Sampler(sampler),
+            texture: None,
+        }
+    }
+
+    pub fn texture(binding: u32, texture: &'a wgpu::Texture, view: &'a wgpu::TextureView) -> Self {
+        Self {
+            binding,
+            resource: wgpu::BindingResource::TextureView(view),
+            texture: Some(texture),
+        }
+    }
+}
+
+/// Named bind group layouts derived from the bindings shaders declare, kept
+/// along with those bindings so pipelines can find the layouts matching their
+/// shaders and bind groups can be checked against them.
+#[derive(Default)]
+pub struct BindGroupLayouts {
+    layouts: HashMap<String, (Vec<ShaderBinding>, wgpu::BindGroupLayout)>,
+}
+
+impl BindGroupLayouts {
+    /// Creates a layout from the bindings of a group shared by several shaders,
+    /// visible to every stage using them.
+    pub fn derive(
+        &mut self,
+        device: &wgpu::Device,
+        name: &str,
+        groups: &[(&Reflection, u32)],
+    ) -> anyhow::Result<&wgpu::BindGroupLayout> {
+        self.derive_with_offsets(device, name, groups, false)
+    }
+
+    /// Like [`Self::derive`], with the uniform buffers bound at dynamic offsets
+    /// so that one bind group can select values in a
+    /// [`UniformRing`](super::uniform::UniformRing) per draw.
+    pub fn derive_dynamic(
+        &mut self,
+        device: &wgpu::Device,
+        name: &str,
+        groups: &[(&Reflection, u32)],
+    ) -> anyhow::Result<&wgpu::BindGroupLayout> {
+        self.derive_with_offsets(device, name, groups, true)
+    }
+
+    fn derive_with_offsets(
+        &mut self,
+        device: &wgpu::Device,
+        name: &str,
+        groups: &[(&Reflection, u32)],
+        dynamic: bool,
+    ) -> anyhow::Result<&wgpu::BindGroupLayout> {
+        let mut bindings: BTreeMap<u32, ShaderBinding> = BTreeMap::new();
+
+        for binding in groups
+            .iter()
+            .flat_map(|(reflection, group)| reflection.group(*group))
+        {
+            let Some(merged) = bindings.get_mut(&binding.binding) else {
+                bindings.insert(binding.binding, binding.clone());
+                continue;
+            };
+
+            merged.ty = reflection::merge(&merged.ty, &binding.ty).ok_or_else(|| {
+                anyhow::anyhow!(
+                    "{name}: binding {} is declared as {} ({:?}) and {} ({:?})",
+                    binding.binding,
+                    merged.name,
+                    merged.ty,
+                    binding.name,
+                    binding.ty
+                )
+            })?;
+            merged.visibility |= binding.visibility;
+        }
+
+        let mut bindings: Vec<ShaderBinding> = bindings.into_values().collect();
+        if dynamic {
+            for binding in &mut bindings {
+                if let wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset,
+                    ..
+                } = &mut binding.ty
+                {
+                    *has_dynamic_offset = true;
+                }
+            }
+        }
+        let entries: Vec<_> = bindings
+            .iter()
+            .map(|binding| wgpu::BindGroupLayoutEntry {
+                binding: binding.binding,
+                visibility: binding.visibility,
+                ty: binding.ty,
+                count: None,
+            })
+            .collect();
+
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some(&format!("{name}_bind_layout")),
+            entries: &entries,
+        });
+
+        Ok(&self
+            .layouts
+            .entry(name.to_string())
+            .insert_entry((bindings, layout))
+            .into_mut()
+            .1)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&wgpu::BindGroupLayout> {
+        self.layouts.get(name).map(|(_, layout)| layout)
+    }
+
+    /// Creates a bind group for the named layout after checking that every
+    /// binding gets a resource of the type the shaders expect.
+    pub fn create_bind_group(
+        &self,
+        device: &wgpu::Device,
+        name: &str,
+        entries: &[Binding],
+    ) -> anyhow::Result<wgpu::BindGroup> {
+        let (bindings, layout) = self
+            .layouts
+            .get(name)
+            .ok_or_else(|| anyhow::anyhow!("No bind group layout named {name}"))?;
+
+        for entry in entries {
+            if !bindings.iter().any(|b| b.binding == entry.binding) {
+                anyhow::bail!("{name}: no binding {} in the shaders", entry.binding);
+            }
+        }
+
+        for binding in bindings {
+            let entry = entries
+                .iter()
+                .find(|entry| entry.binding == binding.binding)
+                .ok_or_else(|| {
+                    anyhow::anyhow!(
+                        "{name}: no resource for binding {} ({})",
+                        binding.binding,
+                        binding.name
+                    )
+                })?;
+
+            reflection::check_resource(
+                &binding.ty,
+                &entry.resource,
+                entry.texture,
+                device.features(),
+            )
+            .map_err(|e| {
+                anyhow::anyhow!("{name}: binding {} ({}) {e}", binding.binding, binding.name)
+            })?;
+        }
+
+        let entries: Vec<_> = entries
+            .iter()
+            .map(|entry| wgpu::BindGroupEntry {
+                binding: entry.binding,
+                resource: entry.resource.clone(),
+            })
+            .collect();
+
+        Ok(device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some(&format!("{name}_bind_group")),
+            layout,
+            entries: &entries,
+        }))
+    }
+
+    /// Names of the layouts with exactly the bindings shaders declare in a group,
+    /// bindings declared by several shaders counting once.
+    fn matching(&self, bindings: &[&ShaderBinding]) -> Vec<&str> {
+        let mut numbers: Vec<u32> = bindings.iter().map(|b| b.binding).collect();
+        numbers.sort();
+        numbers.dedup();
+
+        let mut names: Vec<&str> = self
+            .layouts
+            .iter()
+            .filter(|(_, (layout_bindings, _))| {
+                layout_bindings.len() == numbers.len()
+                    && bindings.iter().all(|binding| {
+                        layout_bindings.iter().any(|entry| {
+                            entry.binding == binding.binding
+                                && entry.visibility.contains(binding.visibility)
+                                && reflection::compatible(&entry.ty, &binding.ty)
+                        })
+                    })
+            })
+            .map(|(name, _)| name.as_str())
+            .collect();
+
+        names.sort();
+        names
+    }
+
+    /// Creates a pipeline layout for the stages of `shaders`, each group using
+    /// the layout named in `names` or else the only layout matching the group's bindings.
+    fn pipeline_layout(
+        &self,
+        device: &wgpu::Device,
+        label: &str,
+        shaders: &[&Shader],
+        names: &[(u32, &str)],
+    ) -> anyhow::Result<wgpu::PipelineLayout> {
+        let mut layouts = Vec::new();
+        let group_count = shaders
+            .iter()
+            .map(|shader| shader.reflection.group_count())
+            .max()
+            .unwrap_or(0);
+
+        for group in 0..group_count {
+            let name = match names.iter().find(|(g, _)| *g == group) {
+                Some((_, name)) => *name,
+                None => {
+                    let bindings: Vec<_> = shaders
+                        .iter()
+                        .flat_map(|shader| shader.reflection.group(group))
+                        .collect();
+                    match self.matching(&bindings)[..] {
+                        [name] => name,
+                        [] => anyhow::bail!(
+                            "{label}: no bind group layout matches group {group} ({})",
+                            bindings
+                                .iter()
+                                .map(|b| format!("{}: {}", b.binding, b.name))
+                                .collect::<Vec<_>>()
+                                .join(", ")
+                        ),
+                        ref names => anyhow::bail!(
+                            "{label}: group {group} matches several bind group layouts ({}), \
+                            choose one with bind_group",
+                            names.join(", ")
+                        ),
+                    }
+                }
+            };
+
+            let layout = self
+                .get(name)
+                .ok_or_else(|| anyhow::anyhow!("{label}: no bind group layout named {name}"))?;
+            layouts.push(layout);
+        }
+
+        Ok(
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some(label),
+                bind_group_layouts: &layouts,
+                push_constant_ranges: &[],
+            }),
+        )
+    }
+}
+
+impl std::ops::Index<&str> for BindGroupLayouts {
+    type Output = wgpu::BindGroupLayout;
+
+    fn index(&self, name: &str) -> &Self::Output {
+        self.get(name)
+            .unwrap_or_else(|| panic!("No bind group layout named {name}"))
+    }
+}
+
+/// Common color blending setups.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Blend {
+    Replace,
+    /// `dst = src * c + dst * (1 - c)` with `c` from `set_blend_constant`, e.g. for running averages.
+    Constant,
+}
+
+impl Blend {
+    pub fn state(self) -> Option<wgpu::BlendState> {
+        let component = |src_factor, dst_factor| wgpu::BlendComponent {
+            src_factor,
+            dst_factor,
+            operation: wgpu::BlendOperation::Add,
+        };
+
+        match self {
+            Blend::Replace => None,
+            Blend::Constant => Some(wgpu::BlendState {
+                color: component(
+                    wgpu::BlendFactor::Constant,
+                    wgpu::BlendFactor::OneMinusConstant,
+                ),
+                alpha: component(
+                    wgpu::BlendFactor::Constant,
+                    wgpu::BlendFactor::OneMinusConstant,
+                ),
+            }),
+        }
+    }
+}
+
+/// Starting point for building pipelines sharing a device, bind group layouts,
+/// surface format and pipeline cache.
+pub struct PipelineFactory<'a> {
+    pub device: &'a wgpu::Device,
+    pub layouts: &'a BindGroupLayouts,
+    pub surface_format: wgpu::TextureFormat,
+    pub cache: Option<&'a wgpu::PipelineCache>,
+}
+
+impl<'a> PipelineFactory<'a> {
+    /// Render pipeline with `vs_main` and `fs_main` entry points, drawing
+    /// triangle lists with back face culling into a surface format target,
+    /// tested against a [`Texture::DEPTH_FORMAT`] depth buffer.
+    pub fn render(&self, label: &'a str, shader: &'a Shader) -> RenderPipelineBuilder<'a> {
+        RenderPipelineBuilder {
+            device: self.device,
+            layouts: self.layouts,
+            cache: self.cache,
+            label,
+            shader,
+            vertex_shader: shader,
+            vertex_entry: "vs_main",
+            fragment_entry: Some("fs_main"),
+            buffers: Vec::new(),
+            targets: vec![Some(wgpu::ColorTargetState {
+                format: self.surface_format,
+                blend: None,
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+            default_targets: true,
+            primitive: wgpu::PrimitiveState {
+                cull_mode: Some(wgpu::Face::Back),
+                ..Default::default()
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: Texture::DEPTH_FORMAT,
+                depth_write_enabled: true,
+                depth_compare: wgpu::CompareFunction::Less,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            bind_groups: Vec::new(),
+        }
+    }
+
+    pub fn compute(
+        &self,
+        label: &'a str,
+        shader: &'a Shader,
+        entry_point: &'a str,
+    ) -> ComputePipelineBuilder<'a> {
+        ComputePipelineBuilder {
+            device: self.device,
+            layouts: self.layouts,
+            cache: self.cache,
+            label,
+            shader,
+            entry_point,
+        }
+    }
+}
+
+pub struct RenderPipelineBuilder<'a> {
+    device: &'a wgpu::Device,
+    layouts: &'a BindGroupLayouts,
+    cache: Option<&'a wgpu::PipelineCache>,
+    label: &'a str,
+    shader: &'a Shader,
+    /// Shader of the vertex stage, `shader` unless set by [`Self::vertex_shader`].
+    vertex_shader: &'a Shader,
+    vertex_entry: &'a str,
+    fragment_entry: Option<&'a str>,
+    buffers: Vec<wgpu::VertexBufferLayout<'a>>,
+    targets: Vec<Option<wgpu::ColorTargetState>>,
+    /// Whether `targets` still holds the surface target set by default.
+    default_targets: bool,
+    primitive: wgpu::PrimitiveState,
+    depth_stencil: Option<wgpu::DepthStencilState>,
+    bind_groups: Vec<(u32, &'a str)>,
+}
+
+impl<'a> RenderPipelineBuilder<'a> {
+    pub fn entry_points(mut self, vertex: &'a str, fragment: Option<&'a str>) -> Self {
+        self.vertex_entry = vertex;
+        self.fragment_entry = fragment;
+        self
+    }
+
+    /// Takes the vertex stage from another shader, e.g. a shared fullscreen triangle.
+    pub fn vertex_shader(mut self, shader: &'a Shader) -> Self {
+        self.vertex_shader = shader;
+        self
+    }
+
+    pub fn vertex_buffer(mut self, layout: wgpu::VertexBufferLayout<'a>) -> Self {
+        self.buffers.push(layout);
+        self
+    }
+
+    /// Adds a color target, replacing the default surface target on first use.
+    pub fn target(mut self, format: wgpu::TextureFormat, blend: Blend) -> Self {
+        if self.default_targets {
+            self.targets.clear();
+            self.default_targets = false;
+        }
+
+        self.targets.push(Some(wgpu::ColorTargetState {
+            format,
+            blend: blend.state(),
+            write_mask: wgpu::ColorWrites::ALL,
+        }));
+        self
+    }
+
+    pub fn cull_mode(mut self, cull_mode: Option<wgpu::Face>) -> Self {
+        self.primitive.cull_mode = cull_mode;
+        self
+    }
+
+    /// Changes the depth test, and whether passing fragments write their depth.
+    pub fn depth(mut self, compare: wgpu::CompareFunction, write: bool) -> Self {
+        if let Some(depth_stencil) = &mut self.depth_stencil {
+            depth_stencil.depth_compare = compare;
+            depth_stencil.depth_write_enabled = write;
+        }
+        self
+    }
+
+    pub fn no_depth(mut self) -> Self {
+        self.depth_stencil = None;
+        self
+    }
+
+    /// Uses the named layout for a group instead of finding it from the shader bindings.
+    pub fn bind_group(mut self, group: u32, name: &'a str) -> Self {
+        self.bind_groups.push((group, name));
+        self
+    }
+
+    pub fn build(self) -> anyhow::Result<wgpu::RenderPipeline> {
+        let layout = self.layouts.pipeline_layout(
+            self.device,
+            self.label,
+            &[self.vertex_shader, self.shader],
+            &self.bind_groups,
+        )?;
+
+        checked(self.device, || {
+            self.device
+                .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                    label: Some(self.label),
+                    layout: Some(&layout),
+                    vertex: wgpu::VertexState {
+                        module: &self.vertex_shader.module,
+                        entry_point: Some(self.vertex_entry),
+                        compilation_options: wgpu::PipelineCompilationOptions::default(),
+                        buffers: &self.buffers,
+                    },
+                    primitive: self.primitive,
+                    multisample: wgpu::MultisampleState::default(),
+                    fragment: self.fragment_entry.map(|entry_point| wgpu::FragmentState {
+                        module: &self.shader.module,
+                        entry_point: Some(entry_point),
+                        compilation_options: wgpu::PipelineCompilationOptions::default(),
+                        targets: &self.targets,
+                    }),
+                    depth_stencil: self.depth_stencil.clone(),
+                    multiview: None,
+                    cache: self.cache,
+                })
+        })
+    }
+}
+
+pub struct ComputePipelineBuilder<'a> {
+    device: &'a wgpu::Device,
+    layouts: &'a BindGroupLayouts,
+    cache: Option<&'a wgpu::PipelineCache>,
+    label: &'a str,
+    shader: &'a Shader,
+    entry_point: &'a str,
+}
+
+impl ComputePipelineBuilder<'_> {
+    pub fn build(self) -> anyhow::Result<wgpu::ComputePipeline> {
+        let layout = self
+            .layouts
+            .pipeline_layout(self.device, self.label, &[self.shader], &[])?;
+
+        checked(self.device, || {
+            self.device
+                .create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                    label: Some(self.label),
+                    layout: Some(&layout),
+                    module: &self.shader.module,
+                    entry_point: Some(self.entry_point),
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                    cache: self.cache,
+                })
+        })
+    }
+}
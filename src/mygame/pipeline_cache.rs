@@ -0,0 +1,133 @@
+use std::{
+    hash::{DefaultHasher, Hash, Hasher},
+    path::PathBuf,
+};
+
+use anyhow::Context;
+
+use super::shader::ShaderLibrary;
+
+/// Compiled pipelines persisted between runs, on adapters supporting it.
+///
+/// The data is stored in `SCATTER_CACHE_DIR` (or the user cache directory)
+/// under a name identifying the adapter. It starts with a hash of the driver
+/// and the shader sources, so it is discarded whenever either changes.
+pub struct PipelineCache {
+    cache: Option<wgpu::PipelineCache>,
+    path: Option<PathBuf>,
+    adapter_info: wgpu::AdapterInfo,
+}
+
+impl PipelineCache {
+    pub fn load(
+        device: &wgpu::Device,
+        adapter_info: &wgpu::AdapterInfo,
+        shaders: &ShaderLibrary,
+    ) -> Self {
+        let path = cache_dir()
+            .zip(wgpu::util::pipeline_cache_key(adapter_info))
+            .map(|(dir, key)| dir.join(key))
+            .filter(|_| device.features().contains(wgpu::Features::PIPELINE_CACHE));
+
+        let Some(path) = path else {
+            log::info!("Pipeline caching is not supported by this adapter.");
+            return Self {
+                cache: None,
+                path: None,
+                adapter_info: adapter_info.clone(),
+            };
+        };
+
+        let fingerprint = fingerprint(adapter_info, shaders);
+        let data = match std::fs::read(&path) {
+            Ok(bytes) => match bytes.split_first_chunk() {
+                Some((header, data)) if u64::from_le_bytes(*header) == fingerprint => {
+                    log::info!("Loaded pipeline cache from {}.", path.display());
+                    Some(data.to_vec())
+                }
+                _ => {
+                    log::info!("Shaders or driver changed, discarding the pipeline cache.");
+                    None
+                }
+            },
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+            Err(e) => {
+                log::warn!("Failed to read {}: {e}", path.display());
+                None
+            }
+        };
+
+        // Safety: the data was written by `save` from `get_data` of a cache
+        // created for an adapter with the same cache key.
+        let cache = unsafe {
+            device.create_pipeline_cache(&wgpu::PipelineCacheDescriptor {
+                label: Some("pipeline_cache"),
+                data: data.as_deref(),
+                fallback: true,
+            })
+        };
+
+        Self {
+            cache: Some(cache),
+            path: Some(path),
+            adapter_info: adapter_info.clone(),
+        }
+    }
+
+    pub fn get(&self) -> Option<&wgpu::PipelineCache> {
+        self.cache.as_ref()
+    }
+
+    /// Writes the cache to disk, tagged with the current shader sources.
+    pub fn save(&self, shaders: &ShaderLibrary) -> anyhow::Result<()> {
+        let (Some(cache), Some(path)) = (&self.cache, &self.path) else {
+            return Ok(());
+        };
+        let Some(data) = cache.get_data() else {
+            return Ok(());
+        };
+
+        let mut contents = fingerprint(&self.adapter_info, shaders)
+            .to_le_bytes()
+            .to_vec();
+        contents.extend_from_slice(&data);
+
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)
+                .with_context(|| format!("Failed to create {}", dir.display()))?;
+        }
+
+        // Written next to the cache first so an interrupted write can't corrupt it
+        let temp = path.with_extension("temp");
+        std::fs::write(&temp, &contents)
+            .with_context(|| format!("Failed to write {}", temp.display()))?;
+        std::fs::rename(&temp, path)
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+
+        log::info!(
+            "Saved {} bytes of pipeline cache to {}.",
+            data.len(),
+            path.display()
+        );
+        Ok(())
+    }
+}
+
+fn cache_dir() -> Option<PathBuf> {
+    let var = |name| std::env::var_os(name).map(PathBuf::from);
+
+    var("SCATTER_CACHE_DIR").or_else(|| {
+        var("XDG_CACHE_HOME")
+            .or_else(|| var("HOME").map(|home| home.join(".cache")))
+            .or_else(|| var("LOCALAPPDATA"))
+            .map(|dir| dir.join("scatter"))
+    })
+}
+
+fn fingerprint(adapter_info: &wgpu::AdapterInfo, shaders: &ShaderLibrary) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    adapter_info.driver.hash(&mut hasher);
+    adapter_info.driver_info.hash(&mut hasher);
+    shaders.fingerprint().hash(&mut hasher);
+    hasher.finish()
+}
@@ -0,0 +1,67 @@
+use std::{fs::File, io::BufWriter, path::Path};
+
+use anyhow::Context;
+
+/// 8-bit RGBA pixels, rows top to bottom.
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+    pub rgba: Vec<u8>,
+}
+
+/// Writes 8-bit RGBA pixels, rows top to bottom, as a PNG.
+pub fn write(path: &Path, width: u32, height: u32, rgba: &[u8]) -> anyhow::Result<()> {
+    anyhow::ensure!(
+        rgba.len() == width as usize * height as usize * 4,
+        "expected {width}x{height} RGBA pixels, got {} bytes",
+        rgba.len()
+    );
+
+    let file =
+        File::create(path).with_context(|| format!("Failed to create {}", path.display()))?;
+    let mut encoder = ::png::Encoder::new(BufWriter::new(file), width, height);
+    encoder.set_color(::png::ColorType::Rgba);
+    encoder.set_depth(::png::BitDepth::Eight);
+
+    encoder
+        .write_header()
+        .and_then(|mut writer| writer.write_image_data(rgba))
+        .with_context(|| format!("Failed to write {}", path.display()))
+}
+
+/// Reads a PNG of any color type, converting it to 8-bit RGBA.
+pub fn read(path: &Path) -> anyhow::Result<Image> {
+    let file = File::open(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    decode(file).with_context(|| format!("Failed to decode {}", path.display()))
+}
+
+fn decode(file: File) -> anyhow::Result<Image> {
+    let mut decoder = ::png::Decoder::new(file);
+    // Palettes, transparency and other bit depths become 8-bit gray or RGB with or without alpha
+    decoder.set_transformations(::png::Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info()?;
+
+    let mut pixels = vec![0; reader.output_buffer_size()];
+    let frame = reader.next_frame(&mut pixels)?;
+    pixels.truncate(frame.buffer_size());
+
+    let rgba = match frame.color_type {
+        ::png::ColorType::Grayscale => pixels.iter().flat_map(|&v| [v, v, v, 255]).collect(),
+        ::png::ColorType::GrayscaleAlpha => pixels
+            .chunks_exact(2)
+            .flat_map(|p| [p[0], p[0], p[0], p[1]])
+            .collect(),
+        ::png::ColorType::Rgb => pixels
+            .chunks_exact(3)
+            .flat_map(|p| [p[0], p[1], p[2], 255])
+            .collect(),
+        ::png::ColorType::Rgba => pixels,
+        ::png::ColorType::Indexed => anyhow::bail!("palette wasn't expanded"),
+    };
+
+    Ok(Image {
+        width: frame.width,
+        height: frame.height,
+        rgba,
+    })
+}
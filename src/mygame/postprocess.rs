@@ -0,0 +1,330 @@
+use wgpu::util::DeviceExt;
+
+use super::{
+    pipeline::{BindGroupLayouts, Binding, Blend, PipelineFactory},
+    shader::Shader,
+    texture::Texture,
+};
+
+/// Description of a fullscreen post processing pass.
+///
+/// The shader provides `fs_main` and receives the vertex output of `fullscreen.wgsl`.
+/// Group 0 is owned by the chain: the input texture at binding 0, a linear sampler at
+/// binding 1 and the pass parameters at binding 2. Passes needing more resources
+/// declare them in group 1, bound through [`PostProcessChain::create_extra_bind_group`].
+/// The layouts of both groups are derived from the shader.
+pub struct PostPassDescriptor<'a> {
+    pub label: &'a str,
+    pub shader: Shader,
+    /// Initial contents of the parameter uniform.
+    pub params: &'a [u8],
+}
+
+pub struct PostPass {
+    label: String,
+    pub enabled: bool,
+    params: wgpu::Buffer,
+    shader: Shader,
+    extra_bind_group: Option<wgpu::BindGroup>,
+    /// Pipeline writing to an intermediate target and one writing to the output.
+    intermediate_pipeline: wgpu::RenderPipeline,
+    output_pipeline: wgpu::RenderPipeline,
+    /// Bind groups reading the chain input, followed by one per ping-pong target.
+    input_bind_groups: Vec<wgpu::BindGroup>,
+}
+
+impl PostPass {
+    pub fn set_params(&self, queue: &wgpu::Queue, params: &[u8]) {
+        queue.write_buffer(&self.params, 0, params);
+    }
+
+    /// Name of the layout of group 1, if the pass has one.
+    fn extra_layout(&self) -> Option<String> {
+        (self.shader.reflection.group_count() > 1).then(|| format!("{}_extra", self.label))
+    }
+}
+
+/// Ordered list of fullscreen passes. Each enabled pass reads the output of the
+/// previous one, intermediate results go through a pair of ping-pong textures
+/// and the last enabled pass writes to the output view given to [`Self::render`],
+/// which is cleared to black if every pass is disabled.
+pub struct PostProcessChain {
+    intermediate_format: wgpu::TextureFormat,
+    output_format: wgpu::TextureFormat,
+    layouts: BindGroupLayouts,
+    vertex: Shader,
+    sampler: wgpu::Sampler,
+    targets: Vec<Texture>,
+    passes: Vec<PostPass>,
+}
+
+impl PostProcessChain {
+    pub fn new(
+        device: &wgpu::Device,
+        vertex: Shader,
+        intermediate_format: wgpu::TextureFormat,
+        output_format: wgpu::TextureFormat,
+    ) -> Self {
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("post_sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        Self {
+            intermediate_format,
+            output_format,
+            layouts: BindGroupLayouts::default(),
+            vertex,
+            sampler,
+            targets: Vec::new(),
+            passes: Vec::new(),
+        }
+    }
+
+    /// Appends a pass to the end of the chain. [`Self::resize`] must be called
+    /// before rendering for the pass to get its inputs.
+    pub fn add_pass(
+        &mut self,
+        device: &wgpu::Device,
+        desc: PostPassDescriptor,
+        cache: Option<&wgpu::PipelineCache>,
+    ) -> anyhow::Result<()> {
+        let reflection = &desc.shader.reflection;
+        self.layouts
+            .derive(device, desc.label, &[(reflection, 0)])?;
+        if reflection.group_count() > 1 {
+            let name = format!("{}_extra", desc.label);
+            self.layouts.derive(device, &name, &[(reflection, 1)])?;
+        }
+
+        let params = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(desc.label),
+            contents: desc.params,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let (intermediate_pipeline, output_pipeline) =
+            self.create_pipelines(device, desc.label, &desc.shader, &self.vertex, cache)?;
+
+        self.passes.push(PostPass {
+            label: desc.label.to_string(),
+            enabled: true,
+            params,
+            shader: desc.shader,
+            extra_bind_group: None,
+            intermediate_pipeline,
+            output_pipeline,
+            input_bind_groups: Vec::new(),
+        });
+
+        Ok(())
+    }
+
+    /// Pipelines drawing a pass into an intermediate target and into the output.
+    fn create_pipelines(
+        &self,
+        device: &wgpu::Device,
+        label: &str,
+        shader: &Shader,
+        vertex: &Shader,
+        cache: Option<&wgpu::PipelineCache>,
+    ) -> anyhow::Result<(wgpu::RenderPipeline, wgpu::RenderPipeline)> {
+        let factory = PipelineFactory {
+            device,
+            layouts: &self.layouts,
+            surface_format: self.output_format,
+            cache,
+        };
+        let extra_layout = format!("{label}_extra");
+        let create_pipeline = |format| {
+            let mut builder = factory
+                .render(label, shader)
+                .vertex_shader(vertex)
+                .bind_group(0, label)
+                .target(format, Blend::Replace)
+                .cull_mode(None)
+                .no_depth();
+            if shader.reflection.group_count() > 1 {
+                builder = builder.bind_group(1, &extra_layout);
+            }
+            builder.build()
+        };
+
+        Ok((
+            create_pipeline(self.intermediate_format)?,
+            create_pipeline(self.output_format)?,
+        ))
+    }
+
+    /// Rebuilds the pipelines of a pass from a new fragment shader, keeping the
+    /// old ones on error. The bindings are expected to stay the same.
+    pub fn reload_pass(
+        &mut self,
+        device: &wgpu::Device,
+        label: &str,
+        shader: Shader,
+        cache: Option<&wgpu::PipelineCache>,
+    ) -> anyhow::Result<()> {
+        let Some(index) = self.passes.iter().position(|p| p.label == label) else {
+            anyhow::bail!("No post processing pass named {label}");
+        };
+
+        let (intermediate, output) =
+            self.create_pipelines(device, label, &shader, &self.vertex, cache)?;
+
+        let pass = &mut self.passes[index];
+        pass.shader = shader;
+        pass.intermediate_pipeline = intermediate;
+        pass.output_pipeline = output;
+
+        Ok(())
+    }
+
+    /// Rebuilds the pipelines of every pass with a new vertex shader.
+    pub fn reload_vertex(
+        &mut self,
+        device: &wgpu::Device,
+        vertex: Shader,
+        cache: Option<&wgpu::PipelineCache>,
+    ) -> anyhow::Result<()> {
+        let pipelines = self
+            .passes
+            .iter()
+            .map(|p| self.create_pipelines(device, &p.label, &p.shader, &vertex, cache))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        for (pass, (intermediate, output)) in self.passes.iter_mut().zip(pipelines) {
+            pass.intermediate_pipeline = intermediate;
+            pass.output_pipeline = output;
+        }
+        self.vertex = vertex;
+
+        Ok(())
+    }
+
+    pub fn pass(&self, label: &str) -> Option<&PostPass> {
+        self.passes.iter().find(|p| p.label == label)
+    }
+
+    /// Binds the group 1 resources of a pass, checking them against its shader.
+    /// Needs to be called again whenever the resources are recreated.
+    pub fn create_extra_bind_group(
+        &mut self,
+        device: &wgpu::Device,
+        label: &str,
+        entries: &[Binding],
+    ) -> anyhow::Result<()> {
+        let Some(index) = self.passes.iter().position(|p| p.label == label) else {
+            anyhow::bail!("No post processing pass named {label}");
+        };
+        let Some(layout) = self.passes[index].extra_layout() else {
+            anyhow::bail!("{label} declares no bind group 1");
+        };
+
+        let bind_group = self.layouts.create_bind_group(device, &layout, entries)?;
+        self.passes[index].extra_bind_group = Some(bind_group);
+        Ok(())
+    }
+
+    /// Recreates intermediate targets and input bind groups for a new surface size
+    /// or chain input.
+    pub fn resize(
+        &mut self,
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        input: &Texture,
+    ) -> anyhow::Result<()> {
+        let target_count = self.passes.len().saturating_sub(1).min(2);
+        self.targets = (0..target_count)
+            .map(|_| {
+                Texture::create_render_target(
+                    device,
+                    config,
+                    self.intermediate_format,
+                    Some("post_target"),
+                )
+            })
+            .collect();
+
+        let inputs: Vec<_> = std::iter::once(input).chain(self.targets.iter()).collect();
+
+        for pass in self.passes.iter_mut() {
+            pass.input_bind_groups = inputs
+                .iter()
+                .map(|input| {
+                    self.layouts.create_bind_group(
+                        device,
+                        &pass.label,
+                        &[
+                            Binding::texture(0, &input.texture, &input.view),
+                            Binding::sampler(1, &self.sampler),
+                            Binding::buffer(2, &pass.params),
+                        ],
+                    )
+                })
+                .collect::<anyhow::Result<_>>()?;
+        }
+
+        Ok(())
+    }
+
+    pub fn render(&self, encoder: &mut wgpu::CommandEncoder, output: &wgpu::TextureView) {
+        let enabled: Vec<_> = self.passes.iter().filter(|p| p.enabled).collect();
+        if enabled.is_empty() {
+            // Nothing would write the output, clear it rather than present garbage
+            encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("post_clear"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: output,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            return;
+        }
+
+        for (i, pass) in enabled.iter().enumerate() {
+            let last = i + 1 == enabled.len();
+            let input = if i == 0 { 0 } else { 1 + (i - 1) % 2 };
+            let (target, pipeline) = if last {
+                (output, &pass.output_pipeline)
+            } else {
+                (&self.targets[i % 2].view, &pass.intermediate_pipeline)
+            };
+
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some(&pass.label),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: target,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+
+            render_pass.set_pipeline(pipeline);
+            render_pass.set_bind_group(0, &pass.input_bind_groups[input], &[]);
+            if let Some(extra) = &pass.extra_bind_group {
+                render_pass.set_bind_group(1, extra, &[]);
+            }
+            render_pass.draw(0..3, 0..1);
+        }
+    }
+}
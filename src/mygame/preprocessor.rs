@@ -0,0 +1,322 @@
+use std::collections::HashMap;
+
+use anyhow::Context;
+
+/// WGSL after resolving directives, with the origin of every line.
+///
+/// Supported directives, each on its own line:
+/// - `#include "path.wgsl"` relative to the shader directory, each file is included once
+/// - `#define NAME [value]` and `#undef NAME`, a value replaces `NAME` wherever it appears as an identifier
+/// - `#ifdef NAME`, `#ifndef NAME`, `#else` and `#endif`
+pub struct Preprocessed {
+    pub source: String,
+    /// Files that make up the source, starting with the root.
+    pub files: Vec<String>,
+    /// File index and 1-based line number of each line of `source`.
+    lines: Vec<(usize, usize)>,
+}
+
+impl Preprocessed {
+    /// Maps a byte offset in `source` to the file, line and column it came from,
+    /// along with the text of that line.
+    pub fn locate(&self, offset: usize) -> (&str, usize, usize, &str) {
+        let offset = offset.min(self.source.len());
+        let line_start = self.source[..offset].rfind('\n').map_or(0, |i| i + 1);
+        let index = self.source[..line_start].matches('\n').count();
+        let text = self.source[line_start..].lines().next().unwrap_or("");
+
+        match self.lines.get(index) {
+            Some(&(file, line)) => (&self.files[file], line, offset - line_start + 1, text),
+            None => (&self.files[0], 0, 0, text),
+        }
+    }
+}
+
+struct Condition {
+    active: bool,
+    has_else: bool,
+}
+
+struct Preprocessor<'l> {
+    load: &'l dyn Fn(&str) -> anyhow::Result<String>,
+    defines: HashMap<String, String>,
+    output: Preprocessed,
+}
+
+/// Resolves directives in the shader `name`, reading files through `load`.
+pub fn preprocess(
+    name: &str,
+    defines: &[(&str, &str)],
+    load: &dyn Fn(&str) -> anyhow::Result<String>,
+) -> anyhow::Result<Preprocessed> {
+    let mut preprocessor = Preprocessor {
+        load,
+        defines: defines
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect(),
+        output: Preprocessed {
+            source: String::new(),
+            files: Vec::new(),
+            lines: Vec::new(),
+        },
+    };
+
+    preprocessor.process(name)?;
+    Ok(preprocessor.output)
+}
+
+impl Preprocessor<'_> {
+    fn process(&mut self, name: &str) -> anyhow::Result<()> {
+        if self.output.files.iter().any(|f| f == name) {
+            return Ok(());
+        }
+
+        let source = (self.load)(name)?;
+        let file = self.output.files.len();
+        self.output.files.push(name.to_string());
+
+        let mut conditions: Vec<Condition> = Vec::new();
+
+        for (i, line) in source.lines().enumerate() {
+            let line_number = i + 1;
+            let active = conditions.iter().all(|c| c.active);
+
+            let Some(directive) = line.trim_start().strip_prefix('#') else {
+                if active {
+                    let line = self.substitute(line);
+                    self.output.source.push_str(&line);
+                    self.output.source.push('\n');
+                    self.output.lines.push((file, line_number));
+                }
+                continue;
+            };
+
+            let (keyword, argument) = directive
+                .split_once(char::is_whitespace)
+                .map_or((directive, ""), |(k, a)| (k, a.trim()));
+            let error = |message: &str| anyhow::anyhow!("{name}:{line_number}: {message}");
+
+            match keyword {
+                "ifdef" | "ifndef" => conditions.push(Condition {
+                    active: self.defines.contains_key(argument) == (keyword == "ifdef"),
+                    has_else: false,
+                }),
+                "else" => match conditions.last_mut() {
+                    Some(condition) if !condition.has_else => {
+                        condition.active = !condition.active;
+                        condition.has_else = true;
+                    }
+                    _ => return Err(error("#else without matching #ifdef")),
+                },
+                "endif" => {
+                    conditions
+                        .pop()
+                        .ok_or_else(|| error("#endif without matching #ifdef"))?;
+                }
+                _ if !active => {}
+                "define" => {
+                    let (define, value) = argument
+                        .split_once(char::is_whitespace)
+                        .map_or((argument, ""), |(d, v)| (d, v.trim()));
+                    if define.is_empty() {
+                        return Err(error("#define requires a name"));
+                    }
+                    self.defines.insert(define.to_string(), value.to_string());
+                }
+                "undef" => {
+                    self.defines.remove(argument);
+                }
+                "include" => {
+                    let path = argument
+                        .strip_prefix('"')
+                        .and_then(|a| a.strip_suffix('"'))
+                        .ok_or_else(|| error("#include expects a quoted path"))?;
+                    self.process(path)
+                        .with_context(|| format!("included from {name}:{line_number}"))?;
+                }
+                _ => return Err(error(&format!("unknown directive #{keyword}"))),
+            }
+        }
+
+        if !conditions.is_empty() {
+            anyhow::bail!("{name}: unterminated #ifdef");
+        }
+
+        Ok(())
+    }
+
+    /// Replaces identifiers defined with a value.
+    fn substitute(&self, line: &str) -> String {
+        if self.defines.values().all(String::is_empty) {
+            return line.to_string();
+        }
+
+        let mut output = String::with_capacity(line.len());
+        let mut rest = line;
+
+        while let Some(start) = rest.find(|c: char| c.is_alphabetic() || c == '_') {
+            let (before, from) = rest.split_at(start);
+            let end = from
+                .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+                .unwrap_or(from.len());
+            let identifier = &from[..end];
+            // Suffixes of numbers such as the `u` in `16u` are not identifiers
+            let is_suffix = before.ends_with(|c: char| c.is_ascii_digit());
+
+            output.push_str(before);
+            match self.defines.get(identifier) {
+                Some(value) if !value.is_empty() && !is_suffix => output.push_str(value),
+                _ => output.push_str(identifier),
+            }
+            rest = &from[end..];
+        }
+
+        output.push_str(rest);
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(files: &[(&str, &str)], defines: &[(&str, &str)]) -> anyhow::Result<Preprocessed> {
+        let files: HashMap<_, _> = files.iter().copied().collect();
+        preprocess("main.wgsl", defines, &|name| {
+            files
+                .get(name)
+                .map(|source| source.to_string())
+                .with_context(|| format!("{name} doesn't exist"))
+        })
+    }
+
+    #[test]
+    fn includes_each_file_once() {
+        let preprocessed = run(
+            &[
+                (
+                    "main.wgsl",
+                    "#include \"common.wgsl\"\n#include \"other.wgsl\"\nmain\n",
+                ),
+                ("common.wgsl", "common\n"),
+                ("other.wgsl", "#include \"common.wgsl\"\nother\n"),
+            ],
+            &[],
+        )
+        .unwrap();
+
+        assert_eq!(preprocessed.source, "common\nother\nmain\n");
+        assert_eq!(
+            preprocessed.files,
+            ["main.wgsl", "common.wgsl", "other.wgsl"]
+        );
+    }
+
+    #[test]
+    fn nests_conditions() {
+        let source = "\
+#ifdef A
+a
+#ifndef B
+a_not_b
+#else
+a_b
+#endif
+#else
+not_a
+#ifdef B
+not_a_b
+#endif
+#endif
+";
+        let expand = |defines| run(&[("main.wgsl", source)], defines).unwrap().source;
+
+        assert_eq!(expand(&[("A", "")]), "a\na_not_b\n");
+        assert_eq!(expand(&[("A", ""), ("B", "")]), "a\na_b\n");
+        assert_eq!(expand(&[("B", "")]), "not_a\nnot_a_b\n");
+        assert_eq!(expand(&[]), "not_a\n");
+    }
+
+    #[test]
+    fn substitutes_defines() {
+        let source = "\
+#define SIZE 16u
+#define FLAG
+let a = array<u32, SIZE>();
+let b = SIZE_2 + 1SIZE;
+#ifdef FLAG
+flag
+#endif
+#undef SIZE
+SIZE
+";
+        let preprocessed = run(&[("main.wgsl", source)], &[("COUNT", "4")]).unwrap();
+
+        assert_eq!(
+            preprocessed.source,
+            "let a = array<u32, 16u>();\nlet b = SIZE_2 + 1SIZE;\nflag\nSIZE\n"
+        );
+        let preprocessed = run(&[("main.wgsl", "COUNT\n")], &[("COUNT", "4")]).unwrap();
+        assert_eq!(preprocessed.source, "4\n");
+    }
+
+    #[test]
+    fn locates_offsets_in_included_files() {
+        let preprocessed = run(
+            &[
+                (
+                    "main.wgsl",
+                    "// main\n#include \"common.wgsl\"\nfn main() {}\n",
+                ),
+                (
+                    "common.wgsl",
+                    "#ifdef MISSING\nskipped\n#endif\nfn common() {}\n",
+                ),
+            ],
+            &[],
+        )
+        .unwrap();
+        assert_eq!(
+            preprocessed.source,
+            "// main\nfn common() {}\nfn main() {}\n"
+        );
+
+        let offset = preprocessed.source.find("common()").unwrap();
+        assert_eq!(
+            preprocessed.locate(offset),
+            ("common.wgsl", 4, 4, "fn common() {}")
+        );
+        let offset = preprocessed.source.find("main()").unwrap();
+        assert_eq!(
+            preprocessed.locate(offset),
+            ("main.wgsl", 3, 4, "fn main() {}")
+        );
+    }
+
+    #[test]
+    fn reports_errors() {
+        let error = |files: &[(&str, &str)]| format!("{:#}", run(files, &[]).err().unwrap());
+
+        assert_eq!(
+            error(&[("main.wgsl", "\n#include \"missing.wgsl\"\n")]),
+            "included from main.wgsl:2: missing.wgsl doesn't exist"
+        );
+        assert_eq!(
+            error(&[("main.wgsl", "#ifdef A\na\n")]),
+            "main.wgsl: unterminated #ifdef"
+        );
+        assert_eq!(
+            error(&[("main.wgsl", "#endif\n")]),
+            "main.wgsl:1: #endif without matching #ifdef"
+        );
+        assert_eq!(
+            error(&[("main.wgsl", "#ifdef A\n#else\n#else\n#endif\n")]),
+            "main.wgsl:3: #else without matching #ifdef"
+        );
+        assert_eq!(
+            error(&[("main.wgsl", "#pragma once\n")]),
+            "main.wgsl:1: unknown directive #pragma"
+        );
+    }
+}
@@ -0,0 +1,57 @@
+use wgpu::PresentMode;
+
+/// Present modes in the order they are cycled through.
+const PRESENT_MODES: [PresentMode; 4] = [
+    PresentMode::Fifo,
+    PresentMode::FifoRelaxed,
+    PresentMode::Mailbox,
+    PresentMode::Immediate,
+];
+
+pub fn name(mode: PresentMode) -> &'static str {
+    match mode {
+        PresentMode::Fifo => "fifo",
+        PresentMode::FifoRelaxed => "fifo_relaxed",
+        PresentMode::Mailbox => "mailbox",
+        PresentMode::Immediate => "immediate",
+        PresentMode::AutoVsync => "auto_vsync",
+        PresentMode::AutoNoVsync => "auto_no_vsync",
+    }
+}
+
+pub fn parse(name: &str) -> Option<PresentMode> {
+    PRESENT_MODES
+        .into_iter()
+        .find(|mode| self::name(*mode) == name)
+}
+
+/// `requested` if the surface supports it, otherwise another mode that doesn't
+/// wait for vertical blank when `requested` doesn't, or else Fifo which is
+/// supported everywhere.
+pub fn supported(requested: PresentMode, supported: &[PresentMode]) -> PresentMode {
+    let fallbacks: &[PresentMode] = match requested {
+        PresentMode::FifoRelaxed => &[PresentMode::FifoRelaxed],
+        PresentMode::Mailbox => &[PresentMode::Mailbox, PresentMode::Immediate],
+        PresentMode::Immediate => &[PresentMode::Immediate, PresentMode::Mailbox],
+        _ => &[],
+    };
+
+    fallbacks
+        .iter()
+        .copied()
+        .find(|mode| supported.contains(mode))
+        .unwrap_or(PresentMode::Fifo)
+}
+
+/// The supported mode following `current`.
+pub fn next(current: PresentMode, supported: &[PresentMode]) -> PresentMode {
+    let index = PRESENT_MODES.iter().position(|mode| *mode == current);
+    PRESENT_MODES
+        .iter()
+        .cycle()
+        .skip(index.map_or(0, |i| i + 1))
+        .take(PRESENT_MODES.len())
+        .copied()
+        .find(|mode| supported.contains(mode))
+        .unwrap_or(PresentMode::Fifo)
+}
@@ -0,0 +1,354 @@
+use std::collections::HashSet;
+
+/// A resource binding declared by a shader.
+#[derive(Clone, Debug)]
+pub struct ShaderBinding {
+    pub group: u32,
+    pub binding: u32,
+    pub name: String,
+    pub ty: wgpu::BindingType,
+    /// Stages of the entry points using the binding.
+    pub visibility: wgpu::ShaderStages,
+}
+
+/// Resource bindings of a shader module, found through naga.
+#[derive(Clone, Debug, Default)]
+pub struct Reflection {
+    pub bindings: Vec<ShaderBinding>,
+}
+
+impl Reflection {
+    pub fn new(module: &naga::Module, info: &naga::valid::ModuleInfo) -> anyhow::Result<Self> {
+        let mut bindings = Vec::new();
+
+        // Textures sampled with a filtering sampler in any entry point
+        let filtered: HashSet<_> = (0..module.entry_points.len())
+            .flat_map(|i| &info.get_entry_point(i).sampling_set)
+            .filter(|key| {
+                !matches!(
+                    module.types[module.global_variables[key.sampler].ty].inner,
+                    naga::TypeInner::Sampler { comparison: true }
+                )
+            })
+            .map(|key| key.image)
+            .collect();
+
+        for (handle, var) in module.global_variables.iter() {
+            let Some(resource) = &var.binding else {
+                continue;
+            };
+
+            let name = var.name.clone().unwrap_or_default();
+            let ty = binding_type(module, var, filtered.contains(&handle))
+                .ok_or_else(|| anyhow::anyhow!("unsupported type for binding {name}"))?;
+
+            let visibility = module
+                .entry_points
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| !info.get_entry_point(*i)[handle].is_empty())
+                .fold(wgpu::ShaderStages::NONE, |stages, (_, entry)| {
+                    stages
+                        | match entry.stage {
+                            naga::ShaderStage::Vertex => wgpu::ShaderStages::VERTEX,
+                            naga::ShaderStage::Fragment => wgpu::ShaderStages::FRAGMENT,
+                            naga::ShaderStage::Compute => wgpu::ShaderStages::COMPUTE,
+                        }
+                });
+
+            bindings.push(ShaderBinding {
+                group: resource.group,
+                binding: resource.binding,
+                name,
+                ty,
+                visibility,
+            });
+        }
+
+        bindings.sort_by_key(|b| (b.group, b.binding));
+        Ok(Self { bindings })
+    }
+
+    /// Number of bind groups, including unused ones before the last.
+    pub fn group_count(&self) -> u32 {
+        self.bindings.last().map_or(0, |b| b.group + 1)
+    }
+
+    pub fn group(&self, group: u32) -> impl Iterator<Item = &ShaderBinding> {
+        self.bindings.iter().filter(move |b| b.group == group)
+    }
+}
+
+/// Binding type of a resource variable. Buffers require at least the size of
+/// their type, float textures are filterable when sampled with a filtering sampler.
+fn binding_type(
+    module: &naga::Module,
+    var: &naga::GlobalVariable,
+    filtered: bool,
+) -> Option<wgpu::BindingType> {
+    let buffer = |ty| {
+        Some(wgpu::BindingType::Buffer {
+            ty,
+            has_dynamic_offset: false,
+            min_binding_size: wgpu::BufferSize::new(
+                module.types[var.ty].inner.size(module.to_ctx()).into(),
+            ),
+        })
+    };
+
+    match var.space {
+        naga::AddressSpace::Uniform => buffer(wgpu::BufferBindingType::Uniform),
+        naga::AddressSpace::Storage { access } => buffer(wgpu::BufferBindingType::Storage {
+            read_only: !access.contains(naga::StorageAccess::STORE),
+        }),
+        naga::AddressSpace::Handle => match module.types[var.ty].inner {
+            naga::TypeInner::Sampler { comparison } => {
+                Some(wgpu::BindingType::Sampler(if comparison {
+                    wgpu::SamplerBindingType::Comparison
+                } else {
+                    wgpu::SamplerBindingType::Filtering
+                }))
+            }
+            naga::TypeInner::Image {
+                dim,
+                arrayed,
+                class,
+            } => {
+                let view_dimension = match (dim, arrayed) {
+                    (naga::ImageDimension::D1, _) => wgpu::TextureViewDimension::D1,
+                    (naga::ImageDimension::D2, false) => wgpu::TextureViewDimension::D2,
+                    (naga::ImageDimension::D2, true) => wgpu::TextureViewDimension::D2Array,
+                    (naga::ImageDimension::D3, _) => wgpu::TextureViewDimension::D3,
+                    (naga::ImageDimension::Cube, false) => wgpu::TextureViewDimension::Cube,
+                    (naga::ImageDimension::Cube, true) => wgpu::TextureViewDimension::CubeArray,
+                };
+
+                let (sample_type, multisampled) = match class {
+                    naga::ImageClass::Sampled { kind, multi } => (
+                        match kind {
+                            naga::ScalarKind::Sint => wgpu::TextureSampleType::Sint,
+                            naga::ScalarKind::Uint => wgpu::TextureSampleType::Uint,
+                            _ => wgpu::TextureSampleType::Float {
+                                filterable: filtered,
+                            },
+                        },
+                        multi,
+                    ),
+                    naga::ImageClass::Depth { multi } => (wgpu::TextureSampleType::Depth, multi),
+                    naga::ImageClass::Storage { .. } => return None,
+                };
+
+                Some(wgpu::BindingType::Texture {
+                    sample_type,
+                    view_dimension,
+                    multisampled,
+                })
+            }
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Whether a layout entry can be used for a binding declared by a shader.
+/// Filtering and buffer sizes can't be told from the shader and are not compared.
+pub fn compatible(layout: &wgpu::BindingType, shader: &wgpu::BindingType) -> bool {
+    use wgpu::{BindingType, TextureSampleType};
+
+    match (layout, shader) {
+        (BindingType::Buffer { ty: a, .. }, BindingType::Buffer { ty: b, .. }) => a == b,
+        (
+            BindingType::Texture {
+                sample_type: a,
+                view_dimension: a_dimension,
+                multisampled: a_multisampled,
+            },
+            BindingType::Texture {
+                sample_type: b,
+                view_dimension: b_dimension,
+                multisampled: b_multisampled,
+            },
+        ) => {
+            a_dimension == b_dimension
+                && a_multisampled == b_multisampled
+                && matches!(
+                    (a, b),
+                    (
+                        TextureSampleType::Float { .. },
+                        TextureSampleType::Float { .. }
+                    ) | (TextureSampleType::Depth, TextureSampleType::Depth)
+                        | (TextureSampleType::Sint, TextureSampleType::Sint)
+                        | (TextureSampleType::Uint, TextureSampleType::Uint)
+                )
+        }
+        (BindingType::Sampler(a), BindingType::Sampler(b)) => {
+            (*a == wgpu::SamplerBindingType::Comparison)
+                == (*b == wgpu::SamplerBindingType::Comparison)
+        }
+        _ => false,
+    }
+}
+
+/// Combines the types two shaders declare for the same binding, requiring the
+/// larger buffer size and filtering if either needs them.
+pub fn merge(a: &wgpu::BindingType, b: &wgpu::BindingType) -> Option<wgpu::BindingType> {
+    use wgpu::{BindingType, TextureSampleType};
+
+    if !compatible(a, b) {
+        return None;
+    }
+
+    Some(match (*a, *b) {
+        (
+            BindingType::Buffer {
+                ty,
+                has_dynamic_offset,
+                min_binding_size: a_size,
+            },
+            BindingType::Buffer {
+                min_binding_size: b_size,
+                ..
+            },
+        ) => BindingType::Buffer {
+            ty,
+            has_dynamic_offset,
+            min_binding_size: a_size.max(b_size),
+        },
+        (
+            BindingType::Texture {
+                sample_type: TextureSampleType::Float { filterable: a },
+                view_dimension,
+                multisampled,
+            },
+            BindingType::Texture {
+                sample_type: TextureSampleType::Float { filterable: b },
+                ..
+            },
+        ) => BindingType::Texture {
+            sample_type: TextureSampleType::Float { filterable: a || b },
+            view_dimension,
+            multisampled,
+        },
+        (a, _) => a,
+    })
+}
+
+/// Checks that a resource can be bound where a shader expects `ty`. Texture
+/// views need their `texture` to check its usages, format and sample count.
+pub fn check_resource(
+    ty: &wgpu::BindingType,
+    resource: &wgpu::BindingResource,
+    texture: Option<&wgpu::Texture>,
+    features: wgpu::Features,
+) -> anyhow::Result<()> {
+    use wgpu::{BindingResource, BindingType, BufferBindingType, BufferUsages, TextureSampleType};
+
+    match (ty, resource) {
+        (
+            BindingType::Buffer {
+                ty,
+                min_binding_size,
+                ..
+            },
+            BindingResource::Buffer(binding),
+        ) => {
+            let (usage, kind) = match ty {
+                BufferBindingType::Uniform => (BufferUsages::UNIFORM, "uniform"),
+                BufferBindingType::Storage { .. } => (BufferUsages::STORAGE, "storage"),
+            };
+            if !binding.buffer.usage().contains(usage) {
+                anyhow::bail!(
+                    "expects a {kind} buffer, got a buffer with usages {:?}",
+                    binding.buffer.usage()
+                );
+            }
+
+            let size = binding
+                .size
+                .map_or(binding.buffer.size() - binding.offset, |size| size.get());
+            if let Some(min_size) = min_binding_size {
+                if size < min_size.get() {
+                    anyhow::bail!("expects at least {min_size} bytes, got {size}");
+                }
+            }
+
+            Ok(())
+        }
+        (
+            BindingType::Texture {
+                sample_type,
+                multisampled,
+                ..
+            },
+            BindingResource::TextureView(_),
+        ) => {
+            let texture = texture
+                .ok_or_else(|| anyhow::anyhow!("expects a texture view with its texture"))?;
+            if !texture
+                .usage()
+                .contains(wgpu::TextureUsages::TEXTURE_BINDING)
+            {
+                anyhow::bail!(
+                    "expects a sampled texture, got a texture with usages {:?}",
+                    texture.usage()
+                );
+            }
+
+            // Depth formats are read through their depth aspect, as depth or unfilterable floats
+            let format = texture.format();
+            let aspect = format
+                .has_depth_aspect()
+                .then_some(wgpu::TextureAspect::DepthOnly);
+            let texels = format.sample_type(aspect, Some(features));
+            let matches = matches!(
+                (sample_type, texels),
+                (
+                    TextureSampleType::Float { filterable: true },
+                    Some(TextureSampleType::Float { filterable: true }),
+                ) | (
+                    TextureSampleType::Float { filterable: false },
+                    Some(TextureSampleType::Float { .. } | TextureSampleType::Depth),
+                ) | (TextureSampleType::Depth, Some(TextureSampleType::Depth))
+                    | (TextureSampleType::Sint, Some(TextureSampleType::Sint))
+                    | (TextureSampleType::Uint, Some(TextureSampleType::Uint))
+            );
+            if !matches {
+                anyhow::bail!("expects {sample_type:?} texels, got a {format:?} texture");
+            }
+
+            if *multisampled != (texture.sample_count() > 1) {
+                anyhow::bail!(
+                    "expects a {}multisampled texture, got {} samples",
+                    if *multisampled { "" } else { "non-" },
+                    texture.sample_count()
+                );
+            }
+
+            Ok(())
+        }
+        (BindingType::Sampler(_), BindingResource::Sampler(_)) => Ok(()),
+        (ty, resource) => anyhow::bail!(
+            "expects {}, got {}",
+            describe(ty),
+            describe_resource(resource)
+        ),
+    }
+}
+
+fn describe(ty: &wgpu::BindingType) -> &'static str {
+    match ty {
+        wgpu::BindingType::Buffer { .. } => "a buffer",
+        wgpu::BindingType::Sampler(_) => "a sampler",
+        wgpu::BindingType::Texture { .. } => "a texture view",
+        _ => "an unsupported resource",
+    }
+}
+
+fn describe_resource(resource: &wgpu::BindingResource) -> &'static str {
+    match resource {
+        wgpu::BindingResource::Buffer(_) => "a buffer",
+        wgpu::BindingResource::Sampler(_) => "a sampler",
+        wgpu::BindingResource::TextureView(_) => "a texture view",
+        _ => "an array of resources",
+    }
+}
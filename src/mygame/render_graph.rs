@@ -0,0 +1,586 @@
+use super::texture::Texture;
+
+/// Handle to a resource declared in a [`RenderGraph`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ResourceId(usize);
+
+/// Description of a texture allocated by the graph for the duration of a frame.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TransientDesc {
+    pub label: &'static str,
+    pub size: (u32, u32),
+    pub format: wgpu::TextureFormat,
+    pub usage: wgpu::TextureUsages,
+}
+
+impl TransientDesc {
+    /// Transients with compatible descriptors may share the same memory.
+    fn compatible(&self, other: &TransientDesc) -> bool {
+        self.size == other.size && self.format == other.format && other.usage.contains(self.usage)
+    }
+}
+
+enum Resource<'a> {
+    /// Owned outside the graph. Buffers and textures without a view given
+    /// only take part in dependency tracking.
+    Imported {
+        name: &'static str,
+        view: Option<&'a wgpu::TextureView>,
+        output: bool,
+    },
+    Transient(TransientDesc),
+}
+
+type Execute<'a> = Box<dyn FnOnce(&mut wgpu::CommandEncoder, &GraphResources) + 'a>;
+
+struct Pass<'a> {
+    name: &'static str,
+    reads: Vec<ResourceId>,
+    writes: Vec<ResourceId>,
+    side_effect: bool,
+    execute: Execute<'a>,
+}
+
+/// Textures assigned to the transients of a frame, indexing the pooled textures
+/// followed by the created ones.
+struct Allocations {
+    /// Texture of every transient resource that is used.
+    physical: Vec<Option<usize>>,
+    /// Descriptors of the textures missing from the pool.
+    created: Vec<TransientDesc>,
+    /// Whether each texture is assigned to a transient.
+    used: Vec<bool>,
+}
+
+/// Textures kept alive between frames so transients are not recreated every frame.
+#[derive(Default)]
+pub struct TransientPool {
+    textures: Vec<(TransientDesc, Texture)>,
+}
+
+/// Resources available to a pass while it executes.
+pub struct GraphResources<'g> {
+    imported: Vec<Option<&'g wgpu::TextureView>>,
+    /// Index into the pool for every transient resource.
+    physical: Vec<Option<usize>>,
+    pool: &'g TransientPool,
+}
+
+impl GraphResources<'_> {
+    pub fn texture(&self, id: ResourceId) -> &Texture {
+        let index = self.physical[id.0].expect("resource is not a transient texture");
+        &self.pool.textures[index].1
+    }
+
+    pub fn view(&self, id: ResourceId) -> &wgpu::TextureView {
+        match self.imported[id.0] {
+            Some(view) => view,
+            None => &self.texture(id).view,
+        }
+    }
+}
+
+/// Frame graph of passes declaring the resources they read and write.
+///
+/// On execution passes are sorted topologically, passes whose outputs are never
+/// used are culled and transient textures are allocated from a [`TransientPool`],
+/// sharing memory between transients whose lifetimes do not overlap.
+#[derive(Default)]
+pub struct RenderGraph<'a> {
+    resources: Vec<Resource<'a>>,
+    passes: Vec<Pass<'a>>,
+}
+
+pub struct PassBuilder<'g, 'a> {
+    graph: &'g mut RenderGraph<'a>,
+    name: &'static str,
+    reads: Vec<ResourceId>,
+    writes: Vec<ResourceId>,
+    side_effect: bool,
+}
+
+impl<'a> PassBuilder<'_, 'a> {
+    pub fn read(mut self, id: ResourceId) -> Self {
+        self.reads.push(id);
+        self
+    }
+
+    pub fn write(mut self, id: ResourceId) -> Self {
+        self.writes.push(id);
+        self
+    }
+
+    /// Keeps the pass even if none of its outputs are used, e.g. for readbacks.
+    pub fn side_effect(mut self) -> Self {
+        self.side_effect = true;
+        self
+    }
+
+    pub fn execute(self, execute: impl FnOnce(&mut wgpu::CommandEncoder, &GraphResources) + 'a) {
+        self.graph.passes.push(Pass {
+            name: self.name,
+            reads: self.reads,
+            writes: self.writes,
+            side_effect: self.side_effect,
+            execute: Box::new(execute),
+        });
+    }
+}
+
+impl<'a> RenderGraph<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn import_texture(
+        &mut self,
+        name: &'static str,
+        view: &'a wgpu::TextureView,
+    ) -> ResourceId {
+        self.resources.push(Resource::Imported {
+            name,
+            view: Some(view),
+            output: false,
+        });
+        ResourceId(self.resources.len() - 1)
+    }
+
+    /// Imports a resource accessed by passes directly, used only to order them.
+    pub fn import(&mut self, name: &'static str) -> ResourceId {
+        self.resources.push(Resource::Imported {
+            name,
+            view: None,
+            output: false,
+        });
+        ResourceId(self.resources.len() - 1)
+    }
+
+    pub fn create_texture(&mut self, desc: TransientDesc) -> ResourceId {
+        self.resources.push(Resource::Transient(desc));
+        ResourceId(self.resources.len() - 1)
+    }
+
+    /// Marks an imported resource as a graph output, keeping the passes writing it.
+    pub fn mark_output(&mut self, id: ResourceId) {
+        if let Resource::Imported { output, .. } = &mut self.resources[id.0] {
+            *output = true;
+        }
+    }
+
+    pub fn add_pass<'g>(&'g mut self, name: &'static str) -> PassBuilder<'g, 'a> {
+        PassBuilder {
+            graph: self,
+            name,
+            reads: Vec::new(),
+            writes: Vec::new(),
+            side_effect: false,
+        }
+    }
+
+    /// Checks that every transient is written by a pass before any pass reads it.
+    /// Passes run in declaration order, so reading a transient that only a later
+    /// pass writes makes the two depend on each other.
+    fn validate(&self) -> anyhow::Result<()> {
+        for (i, pass) in self.passes.iter().enumerate() {
+            for &read in &pass.reads {
+                if !matches!(self.resources[read.0], Resource::Transient(_)) {
+                    continue;
+                }
+                let writer = |p: &Pass| p.writes.contains(&read);
+                if self.passes[..i].iter().any(writer) {
+                    continue;
+                }
+
+                let name = self.resource_name(read);
+                match self.passes[i..].iter().find(|p| writer(p)) {
+                    Some(later) => anyhow::bail!(
+                        "Render graph pass {} reads {name} before {} writes it, \
+                         the passes form a cycle",
+                        pass.name,
+                        later.name
+                    ),
+                    None => anyhow::bail!(
+                        "Render graph pass {} reads {name}, which no pass writes",
+                        pass.name
+                    ),
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns which passes contribute to an output or have side effects.
+    fn cull(&self) -> Vec<bool> {
+        let mut pass_refs: Vec<usize> = self.passes.iter().map(|p| p.writes.len()).collect();
+        let mut resource_refs = vec![0usize; self.resources.len()];
+        for pass in &self.passes {
+            for read in &pass.reads {
+                resource_refs[read.0] += 1;
+            }
+        }
+
+        // Passes writing nothing only survive through side effects
+        let mut alive: Vec<bool> = self
+            .passes
+            .iter()
+            .map(|p| p.side_effect || !p.writes.is_empty())
+            .collect();
+        for (pass, _) in self.passes.iter().zip(&alive).filter(|(_, &a)| !a) {
+            for read in &pass.reads {
+                resource_refs[read.0] -= 1;
+            }
+        }
+
+        let mut unused: Vec<usize> = (0..self.resources.len())
+            .filter(|&r| {
+                resource_refs[r] == 0
+                    && !matches!(self.resources[r], Resource::Imported { output: true, .. })
+            })
+            .collect();
+
+        while let Some(resource) = unused.pop() {
+            log::trace!(
+                "Render graph resource {} is unused.",
+                self.resource_name(ResourceId(resource))
+            );
+
+            for (i, pass) in self.passes.iter().enumerate() {
+                if !alive[i] || !pass.writes.contains(&ResourceId(resource)) {
+                    continue;
+                }
+
+                pass_refs[i] -= 1;
+                if pass_refs[i] == 0 && !pass.side_effect {
+                    alive[i] = false;
+                    log::trace!("Culled render graph pass {}.", pass.name);
+
+                    for read in &pass.reads {
+                        resource_refs[read.0] -= 1;
+                        if resource_refs[read.0] == 0 {
+                            unused.push(read.0);
+                        }
+                    }
+                }
+            }
+        }
+
+        alive
+    }
+
+    /// Orders passes so that every access to a resource happens after the
+    /// conflicting accesses declared before it. Ties keep declaration order.
+    fn sort(&self, alive: &[bool]) -> Vec<usize> {
+        let count = self.passes.len();
+        let mut dependents = vec![Vec::new(); count];
+        let mut in_degree = vec![0usize; count];
+
+        let conflicts = |a: &Pass, b: &Pass| {
+            a.writes
+                .iter()
+                .any(|w| b.reads.contains(w) || b.writes.contains(w))
+                || b.writes.iter().any(|w| a.reads.contains(w))
+        };
+
+        for j in (0..count).filter(|&j| alive[j]) {
+            for i in (0..j).filter(|&i| alive[i]) {
+                if conflicts(&self.passes[i], &self.passes[j]) {
+                    dependents[i].push(j);
+                    in_degree[j] += 1;
+                }
+            }
+        }
+
+        let mut ready: Vec<usize> = (0..count)
+            .filter(|&i| alive[i] && in_degree[i] == 0)
+            .collect();
+        let mut order = Vec::with_capacity(count);
+
+        while !ready.is_empty() {
+            let next = ready.remove(ready.iter().enumerate().min_by_key(|(_, &p)| p).unwrap().0);
+            order.push(next);
+
+            for &dependent in &dependents[next] {
+                in_degree[dependent] -= 1;
+                if in_degree[dependent] == 0 {
+                    ready.push(dependent);
+                }
+            }
+        }
+
+        order
+    }
+
+    /// Assigns textures to transients, reusing a texture once the previous
+    /// transient assigned to it is no longer used. `pooled` describes the
+    /// textures available from earlier frames.
+    fn plan_allocations(&self, order: &[usize], pooled: &[TransientDesc]) -> Allocations {
+        // First and last position in `order` each resource is used at
+        let mut lifetimes: Vec<Option<(usize, usize)>> = vec![None; self.resources.len()];
+        for (position, &pass) in order.iter().enumerate() {
+            let pass = &self.passes[pass];
+            for id in pass.reads.iter().chain(pass.writes.iter()) {
+                let lifetime = lifetimes[id.0].get_or_insert((position, position));
+                lifetime.1 = position;
+            }
+        }
+
+        let mut physical = vec![None; self.resources.len()];
+        let mut textures = pooled.to_vec();
+        let mut busy_until: Vec<Option<usize>> = vec![None; textures.len()];
+
+        let mut transients: Vec<(usize, TransientDesc, (usize, usize))> = self
+            .resources
+            .iter()
+            .enumerate()
+            .filter_map(|(i, r)| match (r, lifetimes[i]) {
+                (Resource::Transient(desc), Some(lifetime)) => Some((i, *desc, lifetime)),
+                _ => None,
+            })
+            .collect();
+        transients.sort_by_key(|(_, _, (first, _))| *first);
+
+        for (resource, desc, (first, last)) in transients {
+            let reusable = textures.iter().enumerate().position(|(i, pooled)| {
+                desc.compatible(pooled) && busy_until[i].is_none_or(|until| until < first)
+            });
+
+            let index = reusable.unwrap_or_else(|| {
+                textures.push(desc);
+                busy_until.push(None);
+                textures.len() - 1
+            });
+
+            busy_until[index] = Some(last);
+            physical[resource] = Some(index);
+        }
+
+        Allocations {
+            physical,
+            created: textures.split_off(pooled.len()),
+            used: busy_until.iter().map(Option::is_some).collect(),
+        }
+    }
+
+    /// Assigns pooled textures to transients, creating the missing ones and
+    /// dropping those no transient needed this frame, e.g. after a resize.
+    fn allocate(
+        &self,
+        device: &wgpu::Device,
+        order: &[usize],
+        pool: &mut TransientPool,
+    ) -> Vec<Option<usize>> {
+        let pooled: Vec<_> = pool.textures.iter().map(|(desc, _)| *desc).collect();
+        let allocations = self.plan_allocations(order, &pooled);
+
+        for desc in allocations.created {
+            log::debug!("Allocating transient texture {}.", desc.label);
+            let texture = Texture::from_descriptor(
+                device,
+                &wgpu::TextureDescriptor {
+                    label: Some(desc.label),
+                    size: wgpu::Extent3d {
+                        width: desc.size.0.max(1),
+                        height: desc.size.1.max(1),
+                        depth_or_array_layers: 1,
+                    },
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: wgpu::TextureDimension::D2,
+                    format: desc.format,
+                    usage: desc.usage,
+                    view_formats: &[],
+                },
+            );
+            pool.textures.push((desc, texture));
+        }
+
+        let mut remap = vec![None; allocations.used.len()];
+        for (i, entry) in std::mem::take(&mut pool.textures).into_iter().enumerate() {
+            if allocations.used[i] {
+                remap[i] = Some(pool.textures.len());
+                pool.textures.push(entry);
+            }
+        }
+
+        allocations
+            .physical
+            .into_iter()
+            .map(|p| p.and_then(|p| remap[p]))
+            .collect()
+    }
+
+    /// Compiles the graph and records all surviving passes into `encoder`.
+    /// Nothing is recorded if the graph is invalid.
+    pub fn execute(
+        self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        pool: &mut TransientPool,
+    ) -> anyhow::Result<()> {
+        self.validate()?;
+        let alive = self.cull();
+        let order = self.sort(&alive);
+        let physical = self.allocate(device, &order, pool);
+
+        let imported = self
+            .resources
+            .iter()
+            .map(|r| match r {
+                Resource::Imported { view, .. } => *view,
+                Resource::Transient(_) => None,
+            })
+            .collect();
+
+        let resources = GraphResources {
+            imported,
+            physical,
+            pool,
+        };
+
+        let mut passes: Vec<Option<Pass>> = self.passes.into_iter().map(Some).collect();
+        for index in order {
+            if let Some(pass) = passes[index].take() {
+                encoder.push_debug_group(pass.name);
+                (pass.execute)(encoder, &resources);
+                encoder.pop_debug_group();
+            }
+        }
+
+        Ok(())
+    }
+
+    fn resource_name(&self, id: ResourceId) -> &'static str {
+        match &self.resources[id.0] {
+            Resource::Imported { name, .. } => name,
+            Resource::Transient(desc) => desc.label,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn transient(graph: &mut RenderGraph, label: &'static str) -> ResourceId {
+        graph.create_texture(TransientDesc {
+            label,
+            size: (4, 4),
+            format: wgpu::TextureFormat::Rgba8Unorm,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+        })
+    }
+
+    fn pass(
+        graph: &mut RenderGraph,
+        name: &'static str,
+        reads: &[ResourceId],
+        writes: &[ResourceId],
+    ) {
+        let mut builder = graph.add_pass(name);
+        for &read in reads {
+            builder = builder.read(read);
+        }
+        for &write in writes {
+            builder = builder.write(write);
+        }
+        builder.execute(|_, _| {});
+    }
+
+    #[test]
+    fn culls_passes_whose_outputs_are_unused() {
+        let mut graph = RenderGraph::new();
+        let surface = graph.import("surface");
+        graph.mark_output(surface);
+        let scene = transient(&mut graph, "scene");
+        let unused = transient(&mut graph, "unused");
+        let dead_end = transient(&mut graph, "dead_end");
+
+        pass(&mut graph, "scene", &[], &[scene]);
+        pass(&mut graph, "unread", &[], &[unused]);
+        // Only read by a culled pass, so culled as well
+        pass(&mut graph, "feeds_culled", &[scene], &[dead_end]);
+        pass(&mut graph, "culled", &[dead_end], &[unused]);
+        pass(&mut graph, "present", &[scene], &[surface]);
+        graph
+            .add_pass("readback")
+            .read(scene)
+            .side_effect()
+            .execute(|_, _| {});
+
+        assert_eq!(graph.cull(), [true, false, false, false, true, true]);
+    }
+
+    #[test]
+    fn sorts_topologically_in_declaration_order() {
+        let mut graph = RenderGraph::new();
+        let surface = graph.import("surface");
+        graph.mark_output(surface);
+        let a = transient(&mut graph, "a");
+        let b = transient(&mut graph, "b");
+
+        pass(&mut graph, "write_a", &[], &[a]);
+        pass(&mut graph, "write_b", &[], &[b]);
+        pass(&mut graph, "unused", &[], &[]);
+        pass(&mut graph, "read_b", &[b], &[surface]);
+        pass(&mut graph, "read_a", &[a], &[surface]);
+
+        let alive = graph.cull();
+        assert_eq!(graph.sort(&alive), [0, 1, 3, 4]);
+    }
+
+    #[test]
+    fn reports_missing_producers_and_cycles() {
+        let mut graph = RenderGraph::new();
+        let missing = transient(&mut graph, "missing");
+        pass(&mut graph, "reader", &[missing], &[]);
+        let error = graph.validate().unwrap_err().to_string();
+        assert_eq!(
+            error,
+            "Render graph pass reader reads missing, which no pass writes"
+        );
+
+        let mut graph = RenderGraph::new();
+        let a = transient(&mut graph, "a");
+        let b = transient(&mut graph, "b");
+        pass(&mut graph, "first", &[b], &[a]);
+        pass(&mut graph, "second", &[a], &[b]);
+        let error = graph.validate().unwrap_err().to_string();
+        assert_eq!(
+            error,
+            "Render graph pass first reads b before second writes it, the passes form a cycle"
+        );
+
+        // Imported resources are written outside the graph
+        let mut graph = RenderGraph::new();
+        let imported = graph.import("imported");
+        pass(&mut graph, "reader", &[imported], &[]);
+        assert!(graph.validate().is_ok());
+    }
+
+    #[test]
+    fn shares_textures_between_transients_that_do_not_overlap() {
+        let mut graph = RenderGraph::new();
+        let surface = graph.import("surface");
+        let first = transient(&mut graph, "first");
+        let second = transient(&mut graph, "second");
+        let overlapping = transient(&mut graph, "overlapping");
+
+        pass(&mut graph, "write_first", &[], &[first]);
+        pass(&mut graph, "read_first", &[first], &[surface]);
+        pass(&mut graph, "write_second", &[], &[second]);
+        pass(&mut graph, "write_overlapping", &[], &[overlapping]);
+        pass(&mut graph, "read_both", &[second, overlapping], &[surface]);
+
+        let order = [0, 1, 2, 3, 4];
+        let allocations = graph.plan_allocations(&order, &[]);
+        let physical = &allocations.physical;
+        assert_eq!(physical[first.0], physical[second.0]);
+        assert_ne!(physical[second.0], physical[overlapping.0]);
+        assert_eq!(allocations.created.len(), 2);
+
+        // The next frame reuses the pooled textures
+        let allocations = graph.plan_allocations(&order, &allocations.created);
+        assert!(allocations.created.is_empty());
+        assert_eq!(allocations.used, [true, true]);
+    }
+}
@@ -0,0 +1,578 @@
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+    sync::mpsc,
+};
+
+use anyhow::Context;
+use cgmath::{Deg, Euler, InnerSpace, Point3, Quaternion, Vector3};
+use notify::Watcher;
+use serde::Deserialize;
+
+use super::{atmosphere::Atmosphere, camera::CameraPose, scene_graph::Transform};
+
+/// Scene used when none is given with `--scene`.
+const BUILTIN: &str = include_str!("../scenes/default.toml");
+
+/// Most nodes with a mesh a scene can have, each taking a slot for its model matrix.
+pub const MAX_MESH_NODES: usize = 1024;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum MeshSource {
+    /// Screen covering quad the atmosphere is marched through.
+    Quad,
+    /// Wavefront OBJ file.
+    Model(PathBuf),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct MeshDesc {
+    pub name: String,
+    pub source: MeshSource,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct MaterialDesc {
+    pub name: String,
+    /// Name of one of the scene's textures.
+    pub texture: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct NodeDesc {
+    pub name: String,
+    /// Name of a node defined earlier in the file.
+    pub parent: Option<String>,
+    pub transform: Transform,
+    pub mesh: Option<String>,
+    pub material: Option<String>,
+    /// Degrees per second around the local axes.
+    pub spin: Option<Vector3<f32>>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TextureDesc {
+    pub name: String,
+    pub path: PathBuf,
+}
+
+/// Contents of a scene file, see `src/scenes/default.toml` for the format.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Scene {
+    pub camera: CameraPose,
+    pub atmosphere: Atmosphere,
+    pub textures: Vec<TextureDesc>,
+    pub materials: Vec<MaterialDesc>,
+    pub meshes: Vec<MeshDesc>,
+    /// Nodes of the scene graph, parents before their children.
+    pub nodes: Vec<NodeDesc>,
+}
+
+/// Scene file as written, before validation.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SceneFile {
+    #[serde(default)]
+    camera: CameraFile,
+    sun: Option<SunFile>,
+    atmosphere: Option<AtmosphereFile>,
+    #[serde(default)]
+    texture: Vec<TextureFile>,
+    #[serde(default)]
+    material: Vec<MaterialFile>,
+    #[serde(default)]
+    mesh: Vec<MeshFile>,
+    #[serde(default)]
+    node: Vec<NodeFile>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CameraFile {
+    #[serde(default)]
+    position: [f32; 3],
+    #[serde(default)]
+    yaw: f32,
+    #[serde(default)]
+    pitch: f32,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SunFile {
+    direction: Option<[f32; 3]>,
+    intensity: Option<f32>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct AtmosphereFile {
+    preset: Option<String>,
+    wavelengths: Option<[f32; 3]>,
+    rayleigh_intensity: Option<f32>,
+    density_falloff: Option<f32>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TextureFile {
+    name: String,
+    path: PathBuf,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MaterialFile {
+    name: String,
+    texture: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Shape {
+    Quad,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MeshFile {
+    name: String,
+    shape: Option<Shape>,
+    model: Option<PathBuf>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct NodeFile {
+    name: String,
+    parent: Option<String>,
+    mesh: Option<String>,
+    material: Option<String>,
+    #[serde(default)]
+    position: [f32; 3],
+    #[serde(default)]
+    rotation: [f32; 3],
+    #[serde(default = "unit_scale")]
+    scale: [f32; 3],
+    spin: Option<[f32; 3]>,
+}
+
+fn unit_scale() -> [f32; 3] {
+    [1.0; 3]
+}
+
+impl Scene {
+    /// Loads the scene file at `path`, or the built-in scene without one.
+    pub fn load(path: Option<&Path>) -> anyhow::Result<Self> {
+        let Some(path) = path else {
+            return Self::parse(BUILTIN, Path::new("")).context("Invalid built-in scene");
+        };
+
+        let source = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read the scene {}", path.display()))?;
+        let dir = path.parent().unwrap_or(Path::new(""));
+        Self::parse(&source, dir).with_context(|| format!("Invalid scene {}", path.display()))
+    }
+
+    /// Parses and validates a scene, resolving paths relative to `dir`.
+    pub fn parse(source: &str, dir: &Path) -> anyhow::Result<Self> {
+        let file: SceneFile = toml::from_str(source)?;
+
+        let camera = parse_camera(&file.camera)?;
+
+        let mut atmosphere = match &file.atmosphere {
+            Some(atmosphere) => parse_atmosphere(atmosphere)?,
+            None => Atmosphere::new(),
+        };
+        if let Some(sun) = &file.sun {
+            if let Some(direction) = sun.direction {
+                let direction = Vector3::from(direction);
+                anyhow::ensure!(
+                    direction.magnitude2() != 0.0,
+                    "[sun] direction can't be zero"
+                );
+                atmosphere.sun_direction = direction;
+            }
+            if let Some(intensity) = sun.intensity {
+                anyhow::ensure!(intensity >= 0.0, "[sun] intensity can't be negative");
+                atmosphere.sun_intensity = intensity;
+            }
+        }
+
+        let mut textures: Vec<TextureDesc> = Vec::new();
+        for texture in file.texture {
+            let name = texture.name;
+            anyhow::ensure!(
+                !textures.iter().any(|t| t.name == name),
+                "[[texture]] {name:?} is defined twice"
+            );
+            let path = existing_file(dir, &texture.path)
+                .with_context(|| format!("[[texture]] {name:?}"))?;
+            textures.push(TextureDesc { name, path });
+        }
+
+        let mut materials: Vec<MaterialDesc> = Vec::new();
+        for material in file.material {
+            let name = material.name;
+            anyhow::ensure!(
+                !materials.iter().any(|m| m.name == name),
+                "[[material]] {name:?} is defined twice"
+            );
+            if let Some(texture) = &material.texture {
+                anyhow::ensure!(
+                    textures.iter().any(|t| &t.name == texture),
+                    "[[material]] {name:?} uses the texture {texture:?}, which no [[texture]] defines"
+                );
+            }
+            materials.push(MaterialDesc {
+                name,
+                texture: material.texture,
+            });
+        }
+
+        let mut meshes: Vec<MeshDesc> = Vec::new();
+        for mesh in file.mesh {
+            let mesh = parse_mesh(mesh, dir)?;
+            anyhow::ensure!(
+                !meshes.iter().any(|m| m.name == mesh.name),
+                "[[mesh]] {:?} is defined twice",
+                mesh.name
+            );
+            meshes.push(mesh);
+        }
+
+        let mut nodes: Vec<NodeDesc> = Vec::new();
+        for node in file.node {
+            let node = parse_node(node)?;
+            let name = &node.name;
+            anyhow::ensure!(
+                !nodes.iter().any(|n| &n.name == name),
+                "[[node]] {name:?} is defined twice"
+            );
+            if let Some(parent) = &node.parent {
+                anyhow::ensure!(
+                    nodes.iter().any(|n| &n.name == parent),
+                    "[[node]] {name:?} has the parent {parent:?}, which no [[node]] above defines"
+                );
+            }
+            if let Some(mesh) = &node.mesh {
+                anyhow::ensure!(
+                    meshes.iter().any(|m| &m.name == mesh),
+                    "[[node]] {name:?} uses the mesh {mesh:?}, which no [[mesh]] defines"
+                );
+            }
+            if let Some(material) = &node.material {
+                anyhow::ensure!(
+                    materials.iter().any(|m| &m.name == material),
+                    "[[node]] {name:?} uses the material {material:?}, which no [[material]] defines"
+                );
+            }
+            nodes.push(node);
+        }
+        let drawn = nodes.iter().filter(|node| node.mesh.is_some()).count();
+        anyhow::ensure!(
+            drawn <= MAX_MESH_NODES,
+            "{drawn} nodes have a mesh, at most {MAX_MESH_NODES} can"
+        );
+
+        Ok(Self {
+            camera,
+            atmosphere,
+            textures,
+            materials,
+            meshes,
+            nodes,
+        })
+    }
+
+    /// Files the scene reads besides the scene file.
+    pub fn dependencies(&self) -> impl Iterator<Item = &Path> {
+        let models = self.meshes.iter().filter_map(|mesh| match &mesh.source {
+            MeshSource::Model(path) => Some(path.as_path()),
+            MeshSource::Quad => None,
+        });
+        let textures = self.textures.iter().map(|texture| texture.path.as_path());
+        models.chain(textures)
+    }
+}
+
+/// `path` relative to `dir`, checking that the file exists.
+fn existing_file(dir: &Path, path: &Path) -> anyhow::Result<PathBuf> {
+    let path = dir.join(path);
+    anyhow::ensure!(path.is_file(), "{} doesn't exist", path.display());
+    Ok(path)
+}
+
+fn parse_camera(camera: &CameraFile) -> anyhow::Result<CameraPose> {
+    anyhow::ensure!(
+        camera.pitch.abs() < 90.0,
+        "[camera] pitch should be strictly between -90 and 90 degrees"
+    );
+
+    Ok(CameraPose {
+        eye: Point3::from(camera.position),
+        yaw: Deg(camera.yaw),
+        pitch: Deg(camera.pitch),
+    })
+}
+
+fn parse_atmosphere(file: &AtmosphereFile) -> anyhow::Result<Atmosphere> {
+    let mut atmosphere = match &file.preset {
+        Some(preset) => Atmosphere::preset(preset).with_context(|| {
+            format!(
+                "[atmosphere] preset should be one of {}, not {preset:?}",
+                Atmosphere::PRESETS.join(", ")
+            )
+        })?,
+        None => Atmosphere::new(),
+    };
+
+    if let Some(wavelengths) = file.wavelengths {
+        anyhow::ensure!(
+            wavelengths.iter().all(|&w| w > 0.0),
+            "[atmosphere] wavelengths should be positive"
+        );
+        atmosphere.wavelengths = wavelengths;
+    }
+    if let Some(intensity) = file.rayleigh_intensity {
+        anyhow::ensure!(
+            intensity >= 0.0,
+            "[atmosphere] rayleigh_intensity can't be negative"
+        );
+        atmosphere.rayleigh_intensity = intensity;
+    }
+    if let Some(falloff) = file.density_falloff {
+        anyhow::ensure!(
+            falloff > 0.0,
+            "[atmosphere] density_falloff should be positive"
+        );
+        atmosphere.density_falloff = falloff;
+    }
+
+    Ok(atmosphere)
+}
+
+fn parse_mesh(mesh: MeshFile, dir: &Path) -> anyhow::Result<MeshDesc> {
+    let name = mesh.name;
+    let source = match (mesh.shape, mesh.model) {
+        (Some(Shape::Quad), None) => MeshSource::Quad,
+        (None, Some(model)) => MeshSource::Model(
+            existing_file(dir, &model).with_context(|| format!("[[mesh]] {name:?}"))?,
+        ),
+        (Some(_), Some(_)) => {
+            anyhow::bail!("[[mesh]] {name:?} can't combine a model with a shape")
+        }
+        (None, None) => anyhow::bail!(
+            "[[mesh]] {name:?} needs either `shape = \"quad\"` or `model = \"file.obj\"`"
+        ),
+    };
+
+    Ok(MeshDesc { name, source })
+}
+
+fn parse_node(node: NodeFile) -> anyhow::Result<NodeDesc> {
+    anyhow::ensure!(
+        node.scale.iter().all(|&s| s != 0.0),
+        "[[node]] {:?} scale can't be zero along any axis",
+        node.name
+    );
+    let [x, y, z] = node.rotation;
+
+    Ok(NodeDesc {
+        name: node.name,
+        parent: node.parent,
+        transform: Transform {
+            translation: Vector3::from(node.position),
+            rotation: Quaternion::from(Euler::new(Deg(x), Deg(y), Deg(z))),
+            scale: Vector3::from(node.scale),
+        },
+        mesh: node.mesh,
+        material: node.material,
+        spin: node.spin.map(Vector3::from),
+    })
+}
+
+/// Watches the directory of a scene file, reporting changes to the scene and
+/// the files it references. Files outside the directory aren't watched.
+pub struct SceneWatcher {
+    path: PathBuf,
+    _watcher: notify::RecommendedWatcher,
+    events: mpsc::Receiver<notify::Result<notify::Event>>,
+}
+
+impl SceneWatcher {
+    /// Starts watching, or returns `None` with a warning if the platform can't.
+    pub fn new(path: &Path) -> Option<Self> {
+        let watch = || -> anyhow::Result<Self> {
+            let path = path.canonicalize()?;
+            let dir = path.parent().context("the scene has no directory")?;
+
+            let (sender, events) = mpsc::channel();
+            let mut watcher = notify::recommended_watcher(sender)?;
+            watcher.watch(dir, notify::RecursiveMode::Recursive)?;
+
+            Ok(Self {
+                path,
+                _watcher: watcher,
+                events,
+            })
+        };
+
+        match watch() {
+            Ok(watcher) => {
+                log::info!("Reloading the scene when {} changes.", path.display());
+                Some(watcher)
+            }
+            Err(e) => {
+                log::warn!(
+                    "Failed to watch {}, the scene won't reload: {e:#}",
+                    path.display()
+                );
+                None
+            }
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Whether the scene file or one of the files `scene` uses changed since the last call.
+    pub fn changed(&self, scene: &Scene) -> bool {
+        let dependencies: HashSet<PathBuf> = scene
+            .dependencies()
+            .filter_map(|path| path.canonicalize().ok())
+            .chain([self.path.clone()])
+            .collect();
+
+        // Drain every event so that one edit saved in several writes reloads once
+        let modified: Vec<PathBuf> = self
+            .events
+            .try_iter()
+            .filter_map(Result::ok)
+            .filter(|event| event.kind.is_modify() || event.kind.is_create())
+            .flat_map(|event| event.paths)
+            .collect();
+        modified.iter().any(|path| dependencies.contains(path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(source: &str) -> String {
+        format!("{:#}", Scene::parse(source, Path::new("")).err().unwrap())
+    }
+
+    #[test]
+    fn parses_builtin_scene() {
+        let scene = Scene::load(None).unwrap();
+        assert_eq!(scene.camera.eye, Point3::new(0.0, 0.0, 0.0));
+        assert!(scene.nodes.is_empty());
+    }
+
+    #[test]
+    fn parses_nodes() {
+        let scene = Scene::parse(
+            r#"
+            [[material]]
+            name = "plain"
+            [[mesh]]
+            name = "sign"
+            shape = "quad"
+            [[node]]
+            name = "pivot"
+            [[node]]
+            name = "sign"
+            parent = "pivot"
+            mesh = "sign"
+            material = "plain"
+            scale = [2.0, 1.0, 1.0]
+            "#,
+            Path::new(""),
+        )
+        .unwrap();
+
+        assert_eq!(scene.meshes[0].source, MeshSource::Quad);
+        assert_eq!(scene.nodes.len(), 2);
+        assert_eq!(scene.nodes[1].parent.as_deref(), Some("pivot"));
+        assert_eq!(scene.nodes[1].transform.scale, Vector3::new(2.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn rejects_duplicate_names() {
+        assert_eq!(
+            error("[[node]]\nname = \"a\"\n[[node]]\nname = \"a\""),
+            "[[node]] \"a\" is defined twice"
+        );
+        assert_eq!(
+            error("[[mesh]]\nname = \"a\"\nshape = \"quad\"\n[[mesh]]\nname = \"a\"\nshape = \"quad\""),
+            "[[mesh]] \"a\" is defined twice"
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_references() {
+        assert_eq!(
+            error("[[node]]\nname = \"a\"\nmesh = \"rock\""),
+            "[[node]] \"a\" uses the mesh \"rock\", which no [[mesh]] defines"
+        );
+        assert_eq!(
+            error("[[node]]\nname = \"a\"\nmaterial = \"rock\""),
+            "[[node]] \"a\" uses the material \"rock\", which no [[material]] defines"
+        );
+        assert_eq!(
+            error("[[material]]\nname = \"a\"\ntexture = \"noise\""),
+            "[[material]] \"a\" uses the texture \"noise\", which no [[texture]] defines"
+        );
+    }
+
+    #[test]
+    fn rejects_parent_after_child() {
+        assert_eq!(
+            error("[[node]]\nname = \"child\"\nparent = \"pivot\"\n[[node]]\nname = \"pivot\""),
+            "[[node]] \"child\" has the parent \"pivot\", which no [[node]] above defines"
+        );
+    }
+
+    #[test]
+    fn rejects_zero_scale() {
+        assert_eq!(
+            error("[[node]]\nname = \"a\"\nscale = [1.0, 0.0, 1.0]"),
+            "[[node]] \"a\" scale can't be zero along any axis"
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_meshes() {
+        assert!(
+            error("[[mesh]]\nname = \"a\"\nshape = \"cube\"").contains("unknown variant `cube`")
+        );
+        assert_eq!(
+            error("[[mesh]]\nname = \"a\""),
+            "[[mesh]] \"a\" needs either `shape = \"quad\"` or `model = \"file.obj\"`"
+        );
+        assert_eq!(
+            error("[[mesh]]\nname = \"a\"\nshape = \"quad\"\nmodel = \"a.obj\""),
+            "[[mesh]] \"a\" can't combine a model with a shape"
+        );
+        assert_eq!(
+            error("[[mesh]]\nname = \"a\"\nmodel = \"missing.obj\""),
+            "[[mesh]] \"a\": missing.obj doesn't exist"
+        );
+    }
+
+    #[test]
+    fn limits_pitch() {
+        let pitch =
+            |pitch: f32| Scene::parse(&format!("[camera]\npitch = {pitch:?}"), Path::new(""));
+        assert_eq!(pitch(-89.9).unwrap().camera.pitch, Deg(-89.9));
+        for invalid in [90.0, -90.0, 120.0] {
+            assert_eq!(
+                format!("{:#}", pitch(invalid).err().unwrap()),
+                "[camera] pitch should be strictly between -90 and 90 degrees"
+            );
+        }
+    }
+}
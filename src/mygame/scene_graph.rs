@@ -0,0 +1,161 @@
+use bytemuck::{Pod, Zeroable};
+use cgmath::{Deg, Euler, Matrix4, One, Quaternion, SquareMatrix, Vector3};
+
+use super::{layout::shader_layout, scene::Scene};
+
+/// Placement of a node relative to its parent: scaled, then rotated, then translated.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Transform {
+    pub translation: Vector3<f32>,
+    pub rotation: Quaternion<f32>,
+    pub scale: Vector3<f32>,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self {
+            translation: Vector3::new(0.0, 0.0, 0.0),
+            rotation: Quaternion::one(),
+            scale: Vector3::new(1.0, 1.0, 1.0),
+        }
+    }
+}
+
+impl Transform {
+    pub fn matrix(&self) -> Matrix4<f32> {
+        Matrix4::from_translation(self.translation)
+            * Matrix4::from(self.rotation)
+            * Matrix4::from_nonuniform_scale(self.scale.x, self.scale.y, self.scale.z)
+    }
+}
+
+/// Handle of a node in a [`SceneGraph`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct NodeId(usize);
+
+pub struct Node {
+    pub name: String,
+    transform: Transform,
+    parent: Option<NodeId>,
+    /// Transform to world space, valid after [`SceneGraph::update`].
+    world: Matrix4<f32>,
+    /// Set when the local transform changed since the world one was computed.
+    dirty: bool,
+    /// Names of the mesh drawn at the node and the material it's drawn with.
+    pub mesh: Option<String>,
+    pub material: Option<String>,
+    /// Degrees per second the node turns around its local axes.
+    pub spin: Option<Vector3<f32>>,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod, Zeroable)]
+pub struct ModelUniform {
+    matrix: [[f32; 4]; 4],
+}
+
+shader_layout!(ModelUniform, "Model", matrix);
+
+/// Hierarchy of transformed nodes. World matrices are cached and only
+/// recomputed by [`Self::update`] for nodes that moved, or whose ancestors did.
+///
+/// Nodes are stored after their parents, so a single pass in order sees every
+/// parent's world matrix before its children's.
+#[derive(Default)]
+pub struct SceneGraph {
+    nodes: Vec<Node>,
+}
+
+impl SceneGraph {
+    /// Builds the nodes of a scene, whose validation ensures parents come first.
+    pub fn from_scene(scene: &Scene) -> Self {
+        let mut graph = Self::default();
+        for desc in &scene.nodes {
+            let parent = desc.parent.as_ref().and_then(|name| graph.find(name));
+            let id = graph.add(&desc.name, desc.transform, parent);
+            let node = graph.node_mut(id);
+            node.mesh = desc.mesh.clone();
+            node.material = desc.material.clone();
+            node.spin = desc.spin;
+        }
+        graph.update();
+        graph
+    }
+
+    /// Adds a node without a mesh, at the top level or under `parent`.
+    pub fn add(&mut self, name: &str, transform: Transform, parent: Option<NodeId>) -> NodeId {
+        let id = NodeId(self.nodes.len());
+        self.nodes.push(Node {
+            name: name.to_string(),
+            transform,
+            parent,
+            world: Matrix4::identity(),
+            dirty: true,
+            mesh: None,
+            material: None,
+            spin: None,
+        });
+        id
+    }
+
+    pub fn find(&self, name: &str) -> Option<NodeId> {
+        self.nodes
+            .iter()
+            .position(|node| node.name == name)
+            .map(NodeId)
+    }
+
+    pub fn node(&self, id: NodeId) -> &Node {
+        &self.nodes[id.0]
+    }
+
+    pub fn node_mut(&mut self, id: NodeId) -> &mut Node {
+        &mut self.nodes[id.0]
+    }
+
+    pub fn nodes(&self) -> impl Iterator<Item = (NodeId, &Node)> {
+        self.nodes
+            .iter()
+            .enumerate()
+            .map(|(i, node)| (NodeId(i), node))
+    }
+
+    /// Turns the spinning nodes by `dt` seconds worth of rotation.
+    pub fn animate(&mut self, dt: f32) {
+        for node in &mut self.nodes {
+            let Some(spin) = node.spin else {
+                continue;
+            };
+            let step = spin * dt;
+            node.transform.rotation = node.transform.rotation
+                * Quaternion::from(Euler::new(Deg(step.x), Deg(step.y), Deg(step.z)));
+            node.dirty = true;
+        }
+    }
+
+    /// Recomputes the world matrices of moved nodes and their descendants,
+    /// returning whether any changed.
+    pub fn update(&mut self) -> bool {
+        let mut moved = vec![false; self.nodes.len()];
+        for i in 0..self.nodes.len() {
+            let parent = self.nodes[i].parent.map(|parent| parent.0);
+            if !self.nodes[i].dirty && !parent.is_some_and(|parent| moved[parent]) {
+                continue;
+            }
+
+            let parent_world =
+                parent.map_or(Matrix4::identity(), |parent| self.nodes[parent].world);
+            let node = &mut self.nodes[i];
+            node.world = parent_world * node.transform.matrix();
+            node.dirty = false;
+            moved[i] = true;
+        }
+        moved.contains(&true)
+    }
+
+    pub fn uniform(&self, id: NodeId) -> ModelUniform {
+        ModelUniform {
+            matrix: self.nodes[id.0].world.into(),
+        }
+    }
+}
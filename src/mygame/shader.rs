@@ -0,0 +1,299 @@
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    hash::{DefaultHasher, Hash, Hasher},
+    path::{Path, PathBuf},
+    sync::mpsc,
+};
+
+use anyhow::Context;
+use notify::Watcher;
+use pollster::FutureExt;
+
+use super::{
+    preprocessor::{preprocess, Preprocessed},
+    reflection::Reflection,
+};
+
+/// Shaders compiled into the binary, used when not loading from disk.
+const EMBEDDED: &[(&str, &str)] = &[
+    ("bloom.wgsl", include_str!("../shaders/bloom.wgsl")),
+    (
+        "common/atmosphere.wgsl",
+        include_str!("../shaders/common/atmosphere.wgsl"),
+    ),
+    (
+        "common/bloom.wgsl",
+        include_str!("../shaders/common/bloom.wgsl"),
+    ),
+    (
+        "common/color.wgsl",
+        include_str!("../shaders/common/color.wgsl"),
+    ),
+    (
+        "common/exposure.wgsl",
+        include_str!("../shaders/common/exposure.wgsl"),
+    ),
+    (
+        "common/uniforms.wgsl",
+        include_str!("../shaders/common/uniforms.wgsl"),
+    ),
+    (
+        "debug_view.wgsl",
+        include_str!("../shaders/debug_view.wgsl"),
+    ),
+    ("diffuse.wgsl", include_str!("../shaders/diffuse.wgsl")),
+    ("exposure.wgsl", include_str!("../shaders/exposure.wgsl")),
+    (
+        "fullscreen.wgsl",
+        include_str!("../shaders/fullscreen.wgsl"),
+    ),
+    ("scatter.wgsl", include_str!("../shaders/scatter.wgsl")),
+    ("tonemap.wgsl", include_str!("../shaders/tonemap.wgsl")),
+];
+
+/// Compiled shader along with the bindings it declares.
+pub struct Shader {
+    pub module: wgpu::ShaderModule,
+    pub reflection: Reflection,
+}
+
+/// Source of WGSL shaders, run through the [preprocessor](super::preprocessor).
+///
+/// Debug builds read shaders from `src/shaders` (or `SCATTER_SHADER_DIR`) and
+/// watch the directory, [`Self::changed`] then reports which files were modified
+/// so the pipelines using them can be rebuilt. Release builds use the embedded sources.
+pub struct ShaderLibrary {
+    dir: Option<PathBuf>,
+    _watcher: Option<notify::RecommendedWatcher>,
+    events: Option<mpsc::Receiver<notify::Result<notify::Event>>>,
+    /// Files each loaded shader was built from, including itself.
+    dependencies: RefCell<HashMap<String, Vec<String>>>,
+}
+
+impl ShaderLibrary {
+    pub fn new() -> Self {
+        let dir = std::env::var_os("SCATTER_SHADER_DIR")
+            .map(PathBuf::from)
+            .or_else(|| {
+                cfg!(debug_assertions)
+                    .then(|| Path::new(env!("CARGO_MANIFEST_DIR")).join("src/shaders"))
+            })
+            .and_then(|dir| dir.canonicalize().ok());
+
+        let Some(dir) = dir else {
+            return Self {
+                dir: None,
+                _watcher: None,
+                events: None,
+                dependencies: RefCell::default(),
+            };
+        };
+
+        let (sender, receiver) = mpsc::channel();
+        let watcher = notify::recommended_watcher(sender).and_then(|mut watcher| {
+            watcher.watch(&dir, notify::RecursiveMode::Recursive)?;
+            Ok(watcher)
+        });
+
+        match watcher {
+            Ok(watcher) => {
+                log::info!("Hot-reloading shaders from {}.", dir.display());
+                Self {
+                    dir: Some(dir),
+                    _watcher: Some(watcher),
+                    events: Some(receiver),
+                    dependencies: RefCell::default(),
+                }
+            }
+            Err(e) => {
+                log::warn!(
+                    "Failed to watch {}, shaders won't reload: {e}",
+                    dir.display()
+                );
+                Self {
+                    dir: Some(dir),
+                    _watcher: None,
+                    events: None,
+                    dependencies: RefCell::default(),
+                }
+            }
+        }
+    }
+
+    /// Reads a file without preprocessing it.
+    fn read(&self, name: &str) -> anyhow::Result<String> {
+        if let Some(dir) = &self.dir {
+            let path = dir.join(name);
+            return std::fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read {}", path.display()));
+        }
+
+        EMBEDDED
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, source)| source.to_string())
+            .with_context(|| format!("No embedded shader named {name}"))
+    }
+
+    fn path(&self, name: &str) -> PathBuf {
+        self.dir
+            .as_ref()
+            .map_or(PathBuf::from(name), |dir| dir.join(name))
+    }
+
+    /// Hash of the sources of every shader, changing whenever one is edited.
+    pub fn fingerprint(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        for (name, _) in EMBEDDED {
+            name.hash(&mut hasher);
+            self.read(name).unwrap_or_default().hash(&mut hasher);
+        }
+        hasher.finish()
+    }
+
+    /// Preprocesses a shader and records the files it includes.
+    pub fn source(&self, name: &str, defines: &[(&str, &str)]) -> anyhow::Result<Preprocessed> {
+        let preprocessed = preprocess(name, defines, &|file| self.read(file))?;
+        self.dependencies
+            .borrow_mut()
+            .insert(name.to_string(), preprocessed.files.clone());
+
+        Ok(preprocessed)
+    }
+
+    pub fn load(&self, device: &wgpu::Device, name: &str) -> anyhow::Result<Shader> {
+        self.load_with(device, name, &[])
+    }
+
+    /// Loads and validates a shader with extra defines, returning the naga
+    /// diagnostics on failure.
+    pub fn load_with(
+        &self,
+        device: &wgpu::Device,
+        name: &str,
+        defines: &[(&str, &str)],
+    ) -> anyhow::Result<Shader> {
+        let (preprocessed, module, info) = self.parse(name, defines)?;
+        let reflection =
+            Reflection::new(&module, &info).with_context(|| format!("Failed to reflect {name}"))?;
+
+        Ok(Shader {
+            module: device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some(name),
+                source: wgpu::ShaderSource::Wgsl(preprocessed.source.into()),
+            }),
+            reflection,
+        })
+    }
+
+    /// Bindings declared by a shader, without creating a module for it.
+    pub fn reflection(&self, name: &str, defines: &[(&str, &str)]) -> anyhow::Result<Reflection> {
+        let (_, module, info) = self.parse(name, defines)?;
+        Reflection::new(&module, &info).with_context(|| format!("Failed to reflect {name}"))
+    }
+
+    /// Parsed and validated module of a shader, for reflection.
+    pub fn reflect(&self, name: &str) -> anyhow::Result<naga::Module> {
+        Ok(self.parse(name, &[])?.1)
+    }
+
+    fn parse(
+        &self,
+        name: &str,
+        defines: &[(&str, &str)],
+    ) -> anyhow::Result<(Preprocessed, naga::Module, naga::valid::ModuleInfo)> {
+        let preprocessed = self.source(name, defines)?;
+
+        let module = naga::front::wgsl::parse_str(&preprocessed.source)
+            .map_err(|e| self.diagnostic(&preprocessed, e.message().to_string(), e.labels()))?;
+        let info = naga::valid::Validator::new(
+            naga::valid::ValidationFlags::all(),
+            naga::valid::Capabilities::all(),
+        )
+        .validate(&module)
+        .map_err(|e| {
+            let mut message = e.as_inner().to_string();
+            let mut source = std::error::Error::source(e.as_inner());
+            while let Some(error) = source {
+                message.push_str(&format!(": {error}"));
+                source = error.source();
+            }
+
+            let labels = e.spans().map(|(span, label)| (*span, label.as_str()));
+            self.diagnostic(&preprocessed, message, labels)
+        })?;
+
+        Ok((preprocessed, module, info))
+    }
+
+    /// Formats a naga error with its labels pointing into the original files.
+    fn diagnostic<'a>(
+        &self,
+        preprocessed: &Preprocessed,
+        message: String,
+        labels: impl Iterator<Item = (naga::Span, &'a str)>,
+    ) -> anyhow::Error {
+        let mut text = message;
+        for (span, label) in labels {
+            let Some(range) = span.to_range() else {
+                continue;
+            };
+
+            let (file, line, column, code) = preprocessed.locate(range.start);
+            text.push_str(&format!(
+                "\n  --> {}:{line}:{column}\n   | {}\n   = {label}",
+                self.path(file).display(),
+                code.trim_end()
+            ));
+        }
+
+        anyhow::anyhow!(text)
+    }
+
+    /// Names of the shaders modified since the last call, along with the
+    /// shaders including them.
+    pub fn changed(&self) -> HashSet<String> {
+        let (Some(events), Some(dir)) = (&self.events, &self.dir) else {
+            return HashSet::new();
+        };
+
+        let modified: HashSet<String> = events
+            .try_iter()
+            .filter_map(Result::ok)
+            .filter(|event| event.kind.is_modify() || event.kind.is_create())
+            .flat_map(|event| event.paths)
+            .filter(|path| path.extension().is_some_and(|e| e == "wgsl"))
+            .filter_map(|path| {
+                let relative = path.strip_prefix(dir).ok()?;
+                let components: Vec<_> = relative
+                    .components()
+                    .map(|c| c.as_os_str().to_string_lossy())
+                    .collect();
+                Some(components.join("/"))
+            })
+            .collect();
+
+        let mut changed = modified.clone();
+        changed.extend(
+            self.dependencies
+                .borrow()
+                .iter()
+                .filter(|(_, files)| files.iter().any(|f| modified.contains(f)))
+                .map(|(name, _)| name.clone()),
+        );
+
+        changed
+    }
+}
+
+/// Runs `create` in a validation error scope, turning errors wgpu would
+/// otherwise treat as fatal into an `Err`.
+pub fn checked<T>(device: &wgpu::Device, create: impl FnOnce() -> T) -> anyhow::Result<T> {
+    device.push_error_scope(wgpu::ErrorFilter::Validation);
+    let value = create();
+    match device.pop_error_scope().block_on() {
+        Some(e) => Err(anyhow::anyhow!("{e}")),
+        None => Ok(value),
+    }
+}
@@ -0,0 +1,78 @@
+use bytemuck::{Pod, Zeroable};
+
+use super::layout::shader_layout;
+
+/// Operator used to map HDR scene radiance to display range.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ToneMapper {
+    Aces,
+    AgX,
+    Reinhard,
+    Uncharted2,
+}
+
+impl ToneMapper {
+    pub const ALL: [ToneMapper; 4] = [
+        ToneMapper::Aces,
+        ToneMapper::AgX,
+        ToneMapper::Reinhard,
+        ToneMapper::Uncharted2,
+    ];
+
+    /// Identifier matching the `TONEMAP_*` constants in `tonemap.wgsl`.
+    fn id(self) -> u32 {
+        match self {
+            ToneMapper::Aces => 0,
+            ToneMapper::AgX => 1,
+            ToneMapper::Reinhard => 2,
+            ToneMapper::Uncharted2 => 3,
+        }
+    }
+
+    pub fn next(self) -> Self {
+        Self::ALL[(self.id() as usize + 1) % Self::ALL.len()]
+    }
+}
+
+pub struct ToneMapping {
+    pub operator: ToneMapper,
+    /// Manual exposure compensation in stops.
+    pub exposure_ev: f32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod, Zeroable)]
+pub struct ToneMappingUniform {
+    exposure: f32,
+    mapper: u32,
+    encode_srgb: u32,
+    _padding: u32,
+}
+
+shader_layout!(
+    ToneMappingUniform,
+    "ToneMapping",
+    exposure,
+    mapper,
+    encode_srgb
+);
+
+impl ToneMapping {
+    pub fn new() -> Self {
+        Self {
+            operator: ToneMapper::Aces,
+            exposure_ev: 0.0,
+        }
+    }
+
+    /// `output_format` is the format of the target the tone mapped image is written to.
+    /// Non-sRGB targets get the transfer function applied in the shader.
+    pub fn uniform(&self, output_format: wgpu::TextureFormat) -> ToneMappingUniform {
+        ToneMappingUniform {
+            exposure: self.exposure_ev.exp2(),
+            mapper: self.operator.id(),
+            encode_srgb: !output_format.is_srgb() as u32,
+            _padding: 0,
+        }
+    }
+}
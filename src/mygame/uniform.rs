@@ -0,0 +1,128 @@
+use bytemuck::Pod;
+use wgpu::util::DeviceExt;
+
+use super::pipeline::Binding;
+
+/// A uniform buffer holding a single `T`. Changes are kept on the CPU until
+/// [`Self::flush`], which only writes the buffer if the value differs from the
+/// one last uploaded.
+pub struct UniformBuffer<T: Pod> {
+    buffer: wgpu::Buffer,
+    value: T,
+    dirty: bool,
+}
+
+impl<T: Pod> UniformBuffer<T> {
+    pub fn new(device: &wgpu::Device, label: &str, value: T) -> Self {
+        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(label),
+            contents: bytemuck::bytes_of(&value),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        Self {
+            buffer,
+            value,
+            dirty: false,
+        }
+    }
+
+    pub fn set(&mut self, value: T) {
+        if bytemuck::bytes_of(&value) != bytemuck::bytes_of(&self.value) {
+            self.value = value;
+            self.dirty = true;
+        }
+    }
+
+    /// Uploads the value if it changed since the last flush, returning whether it did.
+    pub fn flush(&mut self, queue: &wgpu::Queue) -> bool {
+        let dirty = std::mem::take(&mut self.dirty);
+        if dirty {
+            queue.write_buffer(&self.buffer, 0, bytemuck::bytes_of(&self.value));
+        }
+        dirty
+    }
+
+    pub fn buffer(&self) -> &wgpu::Buffer {
+        &self.buffer
+    }
+
+    pub fn entry(&self, binding: u32) -> Binding<'_> {
+        Binding::buffer(binding, &self.buffer)
+    }
+}
+
+/// Per-object uniforms packed into one buffer and bound with a dynamic offset.
+///
+/// Values are pushed each frame after [`Self::clear`], each returning the
+/// offset to pass to `set_bind_group`, then uploaded together by [`Self::flush`].
+pub struct UniformRing<T: Pod> {
+    buffer: wgpu::Buffer,
+    /// Distance between values, rounded up to the device's offset alignment.
+    stride: u64,
+    capacity: u32,
+    values: Vec<T>,
+}
+
+impl<T: Pod> UniformRing<T> {
+    pub fn new(device: &wgpu::Device, label: &str, capacity: u32) -> Self {
+        let alignment = device.limits().min_uniform_buffer_offset_alignment as u64;
+        let stride = (std::mem::size_of::<T>() as u64).next_multiple_of(alignment);
+
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(label),
+            size: stride * capacity.max(1) as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        Self {
+            buffer,
+            stride,
+            capacity,
+            values: Vec::with_capacity(capacity as usize),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.values.clear();
+    }
+
+    /// Adds a value for this frame, returning its dynamic offset or `None` if
+    /// the ring is full.
+    pub fn push(&mut self, value: T) -> Option<u32> {
+        if self.values.len() >= self.capacity as usize {
+            return None;
+        }
+
+        let offset = self.values.len() as u64 * self.stride;
+        self.values.push(value);
+        Some(offset as u32)
+    }
+
+    /// Uploads the values pushed since the last [`Self::clear`].
+    pub fn flush(&self, queue: &wgpu::Queue) {
+        if self.values.is_empty() {
+            return;
+        }
+
+        let mut data = vec![0; self.values.len() * self.stride as usize];
+        for (chunk, value) in data.chunks_mut(self.stride as usize).zip(&self.values) {
+            chunk[..std::mem::size_of::<T>()].copy_from_slice(bytemuck::bytes_of(value));
+        }
+        queue.write_buffer(&self.buffer, 0, &data);
+    }
+
+    /// Bind group entry exposing one value at a time, selected by the dynamic offset.
+    pub fn entry(&self, binding: u32) -> Binding<'_> {
+        Binding {
+            binding,
+            resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                buffer: &self.buffer,
+                offset: 0,
+                size: wgpu::BufferSize::new(std::mem::size_of::<T>() as u64),
+            }),
+            texture: None,
+        }
+    }
+}
@@ -0,0 +1,105 @@
+// Physically based bloom: 13-tap downsample and 3x3 tent upsample over a mip chain
+
+#include "fullscreen.wgsl"
+#include "common/color.wgsl"
+#include "common/bloom.wgsl"
+
+@group(0) @binding(0)
+var source: texture_2d<f32>;
+@group(0) @binding(1)
+var source_sampler: sampler;
+@group(0) @binding(2)
+var<uniform> bloom: Bloom;
+
+fn sample_source(uv: vec2<f32>) -> vec3<f32> {
+    return textureSampleLevel(source, source_sampler, uv, 0.0).rgb;
+}
+
+// Weight that suppresses fireflies from very bright pixels
+fn karis_weight(color: vec3<f32>) -> f32 {
+    return 1.0 / (1.0 + luminance(color));
+}
+
+fn soft_threshold(color: vec3<f32>) -> vec3<f32> {
+    let brightness = max(color.r, max(color.g, color.b));
+    var softness = clamp(brightness - bloom.threshold + bloom.knee, 0.0, 2.0 * bloom.knee);
+    softness = softness * softness / (4.0 * bloom.knee + 1e-4);
+    let contribution = max(brightness - bloom.threshold, softness) / max(brightness, 1e-5);
+    return color * contribution;
+}
+
+struct Taps {
+    a: vec3<f32>, b: vec3<f32>, c: vec3<f32>,
+    d: vec3<f32>, e: vec3<f32>, f: vec3<f32>,
+    g: vec3<f32>, h: vec3<f32>, i: vec3<f32>,
+    j: vec3<f32>, k: vec3<f32>,
+    l: vec3<f32>, m: vec3<f32>,
+};
+
+fn downsample_taps(uv: vec2<f32>) -> Taps {
+    let texel = 1.0 / vec2<f32>(textureDimensions(source));
+    var taps: Taps;
+    taps.a = sample_source(uv + texel * vec2(-2.0, -2.0));
+    taps.b = sample_source(uv + texel * vec2(0.0, -2.0));
+    taps.c = sample_source(uv + texel * vec2(2.0, -2.0));
+    taps.d = sample_source(uv + texel * vec2(-2.0, 0.0));
+    taps.e = sample_source(uv);
+    taps.f = sample_source(uv + texel * vec2(2.0, 0.0));
+    taps.g = sample_source(uv + texel * vec2(-2.0, 2.0));
+    taps.h = sample_source(uv + texel * vec2(0.0, 2.0));
+    taps.i = sample_source(uv + texel * vec2(2.0, 2.0));
+    taps.j = sample_source(uv + texel * vec2(-1.0, -1.0));
+    taps.k = sample_source(uv + texel * vec2(1.0, -1.0));
+    taps.l = sample_source(uv + texel * vec2(-1.0, 1.0));
+    taps.m = sample_source(uv + texel * vec2(1.0, 1.0));
+    return taps;
+}
+
+// First downsample from the HDR scene, with thresholding and Karis average
+@fragment
+fn fs_prefilter(in: VertexOutput) -> @location(0) vec4<f32> {
+    let t = downsample_taps(in.uv);
+
+    let g0 = (t.j + t.k + t.l + t.m) * 0.25;
+    let g1 = (t.a + t.b + t.d + t.e) * 0.25;
+    let g2 = (t.b + t.c + t.e + t.f) * 0.25;
+    let g3 = (t.d + t.e + t.g + t.h) * 0.25;
+    let g4 = (t.e + t.f + t.h + t.i) * 0.25;
+
+    let w0 = 0.5 * karis_weight(g0);
+    let w1 = 0.125 * karis_weight(g1);
+    let w2 = 0.125 * karis_weight(g2);
+    let w3 = 0.125 * karis_weight(g3);
+    let w4 = 0.125 * karis_weight(g4);
+
+    let color = (g0 * w0 + g1 * w1 + g2 * w2 + g3 * w3 + g4 * w4) / (w0 + w1 + w2 + w3 + w4);
+    return vec4(soft_threshold(color), 1.0);
+}
+
+@fragment
+fn fs_downsample(in: VertexOutput) -> @location(0) vec4<f32> {
+    let t = downsample_taps(in.uv);
+
+    var color = t.e * 0.125;
+    color += (t.a + t.c + t.g + t.i) * 0.03125;
+    color += (t.b + t.d + t.f + t.h) * 0.0625;
+    color += (t.j + t.k + t.l + t.m) * 0.125;
+    return vec4(color, 1.0);
+}
+
+// Blended onto the next larger mip with a constant blend factor
+@fragment
+fn fs_upsample(in: VertexOutput) -> @location(0) vec4<f32> {
+    let texel = 1.0 / vec2<f32>(textureDimensions(source));
+
+    var color = sample_source(in.uv) * 4.0;
+    color += sample_source(in.uv + texel * vec2(0.0, -1.0)) * 2.0;
+    color += sample_source(in.uv + texel * vec2(-1.0, 0.0)) * 2.0;
+    color += sample_source(in.uv + texel * vec2(1.0, 0.0)) * 2.0;
+    color += sample_source(in.uv + texel * vec2(0.0, 1.0)) * 2.0;
+    color += sample_source(in.uv + texel * vec2(-1.0, -1.0));
+    color += sample_source(in.uv + texel * vec2(1.0, -1.0));
+    color += sample_source(in.uv + texel * vec2(-1.0, 1.0));
+    color += sample_source(in.uv + texel * vec2(1.0, 1.0));
+    return vec4(color / 16.0, 1.0);
+}
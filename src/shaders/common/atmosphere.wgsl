@@ -0,0 +1,27 @@
+// Scattering coefficients and phase functions
+
+const PI: f32 = 3.141592653589;
+
+// Compute Rayleigh scattering coefficient
+fn rayleighScattering(wavelength: vec3<f32>, intensity: f32) -> vec3<f32> {
+    return intensity / pow(wavelength, vec3(4.0)); // 1 / λ^4
+}
+
+// Compute Mie scattering coefficient
+fn mieScattering(wavelength: vec3<f32>) -> vec3<f32> {
+    const mie_intensity: f32 = 0.001; // Adjust for intensity scaling
+    return mie_intensity / wavelength; // 1 / λ (approximation)
+}
+
+// Rayleigh phase function
+fn rayleighPhase(cos_theta: f32) -> f32 {
+    return 3.0 / 4.0 + pow(cos_theta, 2.0);
+}
+
+// Mie phase function (Henyey-Greenstein)
+fn miePhase(cos_theta: f32, g: f32) -> f32 {
+    let left = 3.0 * (1.0 - pow(g, 2.0)) / (2.0 * (2.0 + pow(g, 2.0)));
+    let right = (1 + pow(cos_theta, 2.0)) / pow(1 + pow(g, 2.0) - 2 * g * cos_theta, 1.5);
+
+    return left * right;
+}
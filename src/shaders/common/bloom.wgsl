@@ -0,0 +1,9 @@
+// Bloom settings shared by bloom.wgsl and tone mapping.
+// Must match `BloomUniform` in bloom.rs.
+
+struct Bloom {
+    threshold: f32,
+    knee: f32,
+    intensity: f32,
+    _padding: f32,
+};
@@ -0,0 +1,13 @@
+// Color helpers
+
+// Relative luminance of linear Rec. 709 color
+fn luminance(color: vec3<f32>) -> f32 {
+    return dot(color, vec3(0.2126, 0.7152, 0.0722));
+}
+
+// sRGB transfer function, for targets that don't encode on write
+fn linear_to_srgb(x: vec3<f32>) -> vec3<f32> {
+    let low = x * 12.92;
+    let high = 1.055 * pow(x, vec3(1.0 / 2.4)) - 0.055;
+    return select(high, low, x <= vec3(0.0031308));
+}
@@ -0,0 +1,7 @@
+// Exposure written by exposure.wgsl and read by tone mapping.
+// Must match `ExposureState` in exposure.rs.
+
+struct ExposureState {
+    average_log_luminance: f32,
+    exposure_ev: f32,
+};
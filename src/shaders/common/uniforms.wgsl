@@ -0,0 +1,32 @@
+// Per-frame uniforms shared by every pipeline using the "game_info" bind group.
+// Must match `GameInfo` in mygame.rs, `CameraUniform` in camera.rs and
+// `AtmosphereUniform` in atmosphere.rs.
+
+struct GameInfo {
+    resolution: vec2<u32>,
+    time: f32,
+    delta_time: f32,
+    jitter: vec2<f32>,
+    sample_index: u32,
+};
+
+struct Camera {
+    view: mat4x4<f32>,
+    inverse_view: mat4x4<f32>,
+    view_proj: mat4x4<f32>,
+};
+
+struct Atmosphere {
+    sun_direction: vec3<f32>,
+    sun_intensity: f32,
+    wavelengths: vec3<f32>,
+    rayleigh_intensity: f32,
+    density_falloff: f32,
+};
+
+@group(0) @binding(0)
+var<uniform> game_info: GameInfo;
+@group(0) @binding(1)
+var<uniform> camera: Camera;
+@group(0) @binding(2)
+var<uniform> atmosphere: Atmosphere;
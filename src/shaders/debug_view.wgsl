@@ -0,0 +1,28 @@
+// Shows an intermediate render target in the debug window
+
+#include "fullscreen.wgsl"
+#include "common/color.wgsl"
+
+@group(0) @binding(0)
+var source: texture_2d<f32>;
+@group(0) @binding(1)
+var linear_sampler: sampler;
+
+// Defined when the surface format isn't sRGB
+#ifdef ENCODE_SRGB
+fn encode(x: vec3<f32>) -> vec3<f32> {
+    return linear_to_srgb(x);
+}
+#else
+fn encode(x: vec3<f32>) -> vec3<f32> {
+    return x;
+}
+#endif
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = textureSampleLevel(source, linear_sampler, in.uv, 0.0).rgb;
+    // Plain Reinhard keeps the HDR values readable without depending on exposure
+    let mapped = max(color, vec3(0.0)) / (1.0 + color);
+    return vec4(encode(mapped), 1.0);
+}
@@ -1,11 +1,29 @@
+#include "common/uniforms.wgsl"
+
+// Must match `ModelUniform` in scene_graph.rs, bound at a dynamic offset per node.
+struct Model {
+    matrix: mat4x4<f32>,
+};
+
+@group(1) @binding(0)
+var<uniform> model: Model;
+
+// Texture of the node's material, white for nodes without one.
+@group(2) @binding(0)
+var t_diffuse: texture_2d<f32>;
+@group(2) @binding(1)
+var s_diffuse: sampler;
+
 // Vertex shader
 
 struct VertexInput {
     @location(0) position: vec3<f32>,
+    @location(1) uv: vec2<f32>,
 }
 
 struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
 };
 
 @vertex
@@ -14,7 +32,11 @@ fn vs_main(
 ) -> VertexOutput {
     var out: VertexOutput;
 
-    out.clip_position = vec4(in.position, 1.0);
+    out.clip_position = camera.view_proj * model.matrix * vec4(in.position, 1.0);
+    // Shift by the same subpixel jitter scatter.wgsl marches its rays with
+    let offset = 2.0 * game_info.jitter / vec2<f32>(game_info.resolution);
+    out.clip_position = vec4(out.clip_position.xy - offset * out.clip_position.w, out.clip_position.zw);
+    out.uv = in.uv;
 
     return out;
 }
@@ -23,6 +45,7 @@ fn vs_main(
 
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    return vec4<f32>(1.0, 0.2, 0.1, 1.0);
+    // Unlit, meshes have no normals to shade with yet
+    let albedo = textureSample(t_diffuse, s_diffuse, in.uv);
+    return vec4<f32>(albedo.rgb, 1.0);
 }
- 
\ No newline at end of file
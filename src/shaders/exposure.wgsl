@@ -0,0 +1,121 @@
+// Automatic exposure from a luminance histogram of the HDR scene
+
+#include "common/uniforms.wgsl"
+#include "common/color.wgsl"
+#include "common/exposure.wgsl"
+
+struct AutoExposure {
+    min_log_luminance: f32,
+    log_luminance_range: f32,
+    low_percentile: f32,
+    high_percentile: f32,
+    speed_up: f32,
+    speed_down: f32,
+    key: f32,
+    enabled: u32,
+};
+
+// Set from HISTOGRAM_BINS and HISTOGRAM_WORKGROUP_SIZE in exposure.rs
+#ifndef HISTOGRAM_BINS
+#define HISTOGRAM_BINS 256u
+#endif
+#ifndef WORKGROUP_SIZE
+#define WORKGROUP_SIZE 16u
+#endif
+
+// Below this luminance pixels land in bin 0, which is excluded from the average
+const MIN_LUMINANCE: f32 = 1e-5;
+
+@group(1) @binding(0)
+var hdr: texture_2d<f32>;
+@group(1) @binding(1)
+var<storage, read_write> histogram: array<atomic<u32>, HISTOGRAM_BINS>;
+@group(1) @binding(2)
+var<uniform> params: AutoExposure;
+@group(1) @binding(3)
+var<storage, read_write> state: ExposureState;
+
+var<workgroup> local_histogram: array<atomic<u32>, HISTOGRAM_BINS>;
+var<workgroup> local_counts: array<u32, HISTOGRAM_BINS>;
+
+fn luminance_bin(lum: f32) -> u32 {
+    if (lum < MIN_LUMINANCE) {
+        return 0u;
+    }
+
+    let t = clamp((log2(lum) - params.min_log_luminance) / params.log_luminance_range, 0.0, 1.0);
+    return u32(t * f32(HISTOGRAM_BINS - 2u)) + 1u;
+}
+
+fn bin_log_luminance(bin: u32) -> f32 {
+    let t = (f32(bin - 1u) + 0.5) / f32(HISTOGRAM_BINS - 2u);
+    return params.min_log_luminance + t * params.log_luminance_range;
+}
+
+@compute @workgroup_size(WORKGROUP_SIZE, WORKGROUP_SIZE, 1)
+fn cs_histogram(
+    @builtin(global_invocation_id) global_id: vec3<u32>,
+    @builtin(local_invocation_index) local_index: u32,
+) {
+    // One invocation per bin, asserted next to HISTOGRAM_WORKGROUP_SIZE in exposure.rs
+    atomicStore(&local_histogram[local_index], 0u);
+    workgroupBarrier();
+
+    let size = textureDimensions(hdr);
+    if (global_id.x < size.x && global_id.y < size.y) {
+        let color = textureLoad(hdr, vec2<i32>(global_id.xy), 0).rgb;
+        atomicAdd(&local_histogram[luminance_bin(luminance(color))], 1u);
+    }
+
+    workgroupBarrier();
+    atomicAdd(&histogram[local_index], atomicLoad(&local_histogram[local_index]));
+}
+
+@compute @workgroup_size(HISTOGRAM_BINS, 1, 1)
+fn cs_average(@builtin(local_invocation_index) local_index: u32) {
+    // Read and clear the histogram for the next frame
+    local_counts[local_index] = atomicExchange(&histogram[local_index], 0u);
+    workgroupBarrier();
+
+    if (local_index != 0u) {
+        return;
+    }
+
+    if (params.enabled == 0u) {
+        state.exposure_ev = 0.0;
+        return;
+    }
+
+    var total = 0u;
+    for (var i = 1u; i < HISTOGRAM_BINS; i++) {
+        total += local_counts[i];
+    }
+
+    // Nothing but black, keep the current exposure
+    if (total == 0u) {
+        return;
+    }
+
+    let low = f32(total) * params.low_percentile;
+    let high = f32(total) * params.high_percentile;
+
+    var cumulative = 0.0;
+    var weighted = 0.0;
+    var weight = 0.0;
+    for (var i = 1u; i < HISTOGRAM_BINS; i++) {
+        let count = f32(local_counts[i]);
+        let clipped = clamp(cumulative + count, low, high) - clamp(cumulative, low, high);
+        weighted += clipped * bin_log_luminance(i);
+        weight += clipped;
+        cumulative += count;
+    }
+
+    let average = weighted / max(weight, 1.0);
+    let target_ev = log2(params.key) - average;
+
+    let speed = select(params.speed_down, params.speed_up, target_ev < state.exposure_ev);
+    let factor = 1.0 - exp(-game_info.delta_time * speed);
+
+    state.average_log_luminance = average;
+    state.exposure_ev += (target_ev - state.exposure_ev) * factor;
+}
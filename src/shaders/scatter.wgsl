@@ -1,3 +1,6 @@
+#include "common/uniforms.wgsl"
+#include "common/atmosphere.wgsl"
+
 struct VertexInput {
     @location(0) position: vec3<f32>,
     @location(1) uv: vec2<f32>,
@@ -8,28 +11,13 @@ struct VertexOutput {
     @location(0) uv: vec2<f32>,
 };
 
-struct GameInfo {
-    resolution: vec2<u32>,
-    time: f32,
-    delta_time: f32,
-};
-
-struct Camera {
-    view: mat4x4<f32>,
-    inverse_view: mat4x4<f32>,
-};
-
-@group(0) @binding(0)
-var<uniform> game_info: GameInfo;
-@group(0) @binding(1)
-var<uniform> camera: Camera;
-
 @vertex
 fn vs_main(
     in: VertexInput,
 ) -> VertexOutput {
     var out: VertexOutput;
-    out.clip_position = vec4(in.position, 1.0);
+    // Screen quad at the far plane, drawn after the scene only where no mesh is
+    out.clip_position = vec4(in.position.xy, 1.0, 1.0);
     out.uv = in.uv;
     return out;
 }
@@ -38,9 +26,7 @@ fn vs_main(
 const SKY_LIGHT: vec3<f32> = vec3<f32>(0.0);
 const AABB_MIN: vec3<f32> = vec3<f32>(-40.0);
 const AABB_MAX: vec3<f32> = vec3<f32>(40.0);
-const SUN_DIRECTION: vec3<f32> = vec3<f32>(0.5773502691896258, 0.5773502691896258, 0.5773502691896258);
-const PI: f32 = 3.141592653589;
-const WAVELENGTHS: vec3<f32> = vec3<f32>(0.7, 0.9, 0.80);
+const GOLDEN_RATIO_CONJUGATE: f32 = 0.6180339887498949;
 
 fn aabb_ray(min: vec3<f32>, max: vec3<f32>, ro: vec3<f32>, rd: vec3<f32>) -> vec2<f32> {
     let tMin = (min - ro) / rd;
@@ -67,43 +53,18 @@ fn sample_point(p: vec3<f32>) -> f32 {
 
     let h = length(p) / 20.0;
 
-    return exp(-h / 0.35);
-}
-
-// Compute Rayleigh scattering coefficient
-fn rayleighScattering(wavelength: vec3<f32>) -> vec3<f32> {
-    const rayleigh_intensity: f32 = 0.1; // Adjust for intensity scaling
-    return rayleigh_intensity / pow(wavelength, vec3(4.0)); // 1 / λ^4
-}
-
-// Compute Mie scattering coefficient
-fn mieScattering(wavelength: vec3<f32>) -> vec3<f32> {
-    const mie_intensity: f32 = 0.001; // Adjust for intensity scaling
-    return mie_intensity / wavelength; // 1 / λ (approximation)
-}
-
-// Rayleigh phase function
-fn rayleighPhase(cos_theta: f32) -> f32 {
-    return 3.0 / 4.0 + pow(cos_theta, 2.0);
-}
-
-// Mie phase function (Henyey-Greenstein)
-fn miePhase(cos_theta: f32, g: f32) -> f32 {
-    let left = 3.0 * (1.0 - pow(g, 2.0)) / (2.0 * (2.0 + pow(g, 2.0)));
-    let right = (1 + pow(cos_theta, 2.0)) / pow(1 + pow(g, 2.0) - 2 * g * cos_theta, 1.5);
-
-    return left * right;
+    return exp(-h / atmosphere.density_falloff);
 }
 
 fn ray_sky(rd: vec3<f32>) -> vec3<f32> {
-    let sun = pow(clamp(dot(rd, SUN_DIRECTION), 0.0, 1.0), 128.0);
+    let sun = pow(clamp(dot(rd, atmosphere.sun_direction), 0.0, 1.0), 128.0);
 
-    return mix(SKY_LIGHT, vec3<f32>(1.0), sun);
+    return mix(SKY_LIGHT, vec3<f32>(atmosphere.sun_intensity), sun);
 }
 
 fn out_scattering(p0: vec3<f32>, p1: vec3<f32>) -> vec3<f32> {
-    let rayleigh = rayleighScattering(WAVELENGTHS);
-    let mie = mieScattering(WAVELENGTHS);
+    let rayleigh = rayleighScattering(atmosphere.wavelengths, atmosphere.rayleigh_intensity);
+    let mie = mieScattering(atmosphere.wavelengths);
 
     let step_count = 8;
     let h = p1 - p0;
@@ -131,8 +92,8 @@ fn out_scattering(p0: vec3<f32>, p1: vec3<f32>) -> vec3<f32> {
 }
 
 fn in_scattering(p0: vec3<f32>, p1: vec3<f32>) -> vec3<f32> {
-    let rayleigh = rayleighScattering(WAVELENGTHS);
-    let mie = mieScattering(WAVELENGTHS);
+    let rayleigh = rayleighScattering(atmosphere.wavelengths, atmosphere.rayleigh_intensity);
+    let mie = mieScattering(atmosphere.wavelengths);
 
     let step_count = 256;
     let h = p1 - p0;
@@ -140,9 +101,10 @@ fn in_scattering(p0: vec3<f32>, p1: vec3<f32>) -> vec3<f32> {
     let step_size = length(h) / f32(step_count);
 
     var accumulated_scattering = vec3<f32>(0.0);
-    var t = 0.0;
+    // Offset the march start per accumulated sample to stratify the integral
+    var t = fract(0.5 + f32(game_info.sample_index) * GOLDEN_RATIO_CONJUGATE) * step_size;
 
-    let cos_theta = dot(d, SUN_DIRECTION);
+    let cos_theta = dot(d, atmosphere.sun_direction);
     let rayleigh_phase = rayleighPhase(cos_theta);
 
     var steps = 0;
@@ -152,9 +114,9 @@ fn in_scattering(p0: vec3<f32>, p1: vec3<f32>) -> vec3<f32> {
         let p = p0 + t * d;
         let density = sample_point(p);
 
-        let sun_dir_intersection = aabb_ray(AABB_MIN, AABB_MAX, p, SUN_DIRECTION);
+        let sun_dir_intersection = aabb_ray(AABB_MIN, AABB_MAX, p, atmosphere.sun_direction);
         let cam_dir_intersection = aabb_ray(AABB_MIN, AABB_MAX, p, -d);
-        let out_scatter_sun = out_scattering(p, p + SUN_DIRECTION * (sun_dir_intersection.y + 0.1));
+        let out_scatter_sun = out_scattering(p, p + atmosphere.sun_direction * (sun_dir_intersection.y + 0.1));
         let out_scatter_camera = out_scattering(p, p + (-d * cam_dir_intersection.y));
 
         let sun_camera_scatter = exp(-out_scatter_sun);
@@ -200,8 +162,9 @@ fn calculate_pixel(ro: vec3<f32>, rd: vec3<f32>) -> vec3<f32> {
 
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    let aspect = f32(game_info.resolution.x) / f32(game_info.resolution.y);
-    var uv = in.uv * 2.0 - 1.0;
+    let resolution = vec2<f32>(game_info.resolution);
+    let aspect = resolution.x / resolution.y;
+    var uv = (in.uv + game_info.jitter / resolution) * 2.0 - 1.0;
     uv.x *= aspect;
 
     let ro = (camera.inverse_view * vec4(0.0, 0.0, 0.0, 1.0)).xyz;
@@ -0,0 +1,124 @@
+// Maps the HDR scene onto the surface, post processing pass
+
+#include "fullscreen.wgsl"
+#include "common/color.wgsl"
+#include "common/exposure.wgsl"
+#include "common/bloom.wgsl"
+
+struct ToneMapping {
+    exposure: f32,
+    mapper: u32,
+    encode_srgb: u32,
+    _padding: u32,
+};
+
+const TONEMAP_ACES: u32 = 0u;
+const TONEMAP_AGX: u32 = 1u;
+const TONEMAP_REINHARD: u32 = 2u;
+const TONEMAP_UNCHARTED2: u32 = 3u;
+
+@group(0) @binding(0)
+var hdr: texture_2d<f32>;
+@group(0) @binding(1)
+var linear_sampler: sampler;
+@group(0) @binding(2)
+var<uniform> tone_mapping: ToneMapping;
+
+@group(1) @binding(0)
+var<storage, read> exposure: ExposureState;
+@group(1) @binding(1)
+var bloom_texture: texture_2d<f32>;
+@group(1) @binding(2)
+var<uniform> bloom: Bloom;
+
+// Narkowicz fit of the ACES filmic curve
+fn aces(x: vec3<f32>) -> vec3<f32> {
+    let a = 2.51;
+    let b = 0.03;
+    let c = 2.43;
+    let d = 0.59;
+    let e = 0.14;
+    return clamp((x * (a * x + b)) / (x * (c * x + d) + e), vec3(0.0), vec3(1.0));
+}
+
+fn agx_contrast(x: vec3<f32>) -> vec3<f32> {
+    let x2 = x * x;
+    let x4 = x2 * x2;
+    return 15.5 * x4 * x2 - 40.14 * x4 * x + 31.96 * x4 - 6.868 * x2 * x + 0.4298 * x2 + 0.1191 * x - 0.00232;
+}
+
+// Minimal AgX with the default look, returns linear values
+fn agx(x: vec3<f32>) -> vec3<f32> {
+    let inset = mat3x3<f32>(
+        vec3(0.842479062253094, 0.0423282422610123, 0.0423756549057051),
+        vec3(0.0784335999999992, 0.878468636469772, 0.0784336),
+        vec3(0.0792237451477643, 0.0791661274605434, 0.879142973793104),
+    );
+    let outset = mat3x3<f32>(
+        vec3(1.19687900512017, -0.0528968517574562, -0.0529716355144438),
+        vec3(-0.0980208811401368, 1.15190312990417, -0.0980434501171241),
+        vec3(-0.0990297440797205, -0.0989611768448433, 1.15107367264116),
+    );
+    let min_ev = -12.47393;
+    let max_ev = 4.026069;
+
+    var c = inset * x;
+    c = clamp(log2(max(c, vec3(1e-10))), vec3(min_ev), vec3(max_ev));
+    c = (c - min_ev) / (max_ev - min_ev);
+    c = agx_contrast(c);
+    c = outset * c;
+
+    return pow(max(c, vec3(0.0)), vec3(2.2));
+}
+
+fn reinhard(x: vec3<f32>) -> vec3<f32> {
+    return x / (1.0 + x);
+}
+
+fn hable(x: vec3<f32>) -> vec3<f32> {
+    let a = 0.15;
+    let b = 0.50;
+    let c = 0.10;
+    let d = 0.20;
+    let e = 0.02;
+    let f = 0.30;
+    return ((x * (a * x + c * b) + d * e) / (x * (a * x + b) + d * f)) - e / f;
+}
+
+fn uncharted2(x: vec3<f32>) -> vec3<f32> {
+    let exposure_bias = 2.0;
+    let white = 11.2;
+    return hable(x * exposure_bias) / hable(vec3(white));
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let scene = textureLoad(hdr, vec2<i32>(in.clip_position.xy), 0).rgb;
+    let glow = textureSampleLevel(bloom_texture, linear_sampler, in.uv, 0.0).rgb;
+
+    let exposure_scale = tone_mapping.exposure * exp2(exposure.exposure_ev);
+    let color = mix(scene, glow, bloom.intensity) * exposure_scale;
+
+    var mapped: vec3<f32>;
+    switch tone_mapping.mapper {
+        case TONEMAP_AGX: {
+            mapped = agx(color);
+        }
+        case TONEMAP_REINHARD: {
+            mapped = reinhard(color);
+        }
+        case TONEMAP_UNCHARTED2: {
+            mapped = uncharted2(color);
+        }
+        default: {
+            mapped = aces(color);
+        }
+    }
+
+    mapped = clamp(mapped, vec3(0.0), vec3(1.0));
+    if (tone_mapping.encode_srgb != 0u) {
+        mapped = linear_to_srgb(mapped);
+    }
+
+    return vec4(mapped, 1.0);
+}
@@ -1,18 +1,154 @@
-use std::sync::Arc;
+use std::{sync::Arc, time::Instant};
+
+use anyhow::Context;
 
 use winit::{
     application::ApplicationHandler,
+    dpi::{PhysicalPosition, PhysicalSize},
     event::WindowEvent,
-    window::{Window, WindowAttributes},
+    event_loop::{ActiveEventLoop, ControlFlow},
+    window::{Fullscreen, Icon, Window, WindowAttributes, WindowId},
 };
 
-pub trait Game {
-    fn init(window: Arc<Window>) -> Self;
+/// Duration of a simulation step in seconds.
+pub const FIXED_TIMESTEP: f32 = 1.0 / 120.0;
+/// Longest frame time simulated at once. Slower frames slow the simulation down
+/// rather than running ever more steps to catch up.
+const MAX_FRAME_TIME: f32 = 0.25;
+
+/// How the game window is created.
+#[derive(Clone, Debug)]
+pub struct WindowConfig {
+    pub title: String,
+    /// Inner size in physical pixels, left to the platform if `None`.
+    pub size: Option<PhysicalSize<u32>>,
+    /// Outer position in physical pixels. Ignored when it's not on any monitor,
+    /// e.g. after unplugging the monitor it was saved on.
+    pub position: Option<PhysicalPosition<i32>>,
+    pub resizable: bool,
+    pub icon: Option<Icon>,
+    /// Starts in borderless fullscreen rather than windowed.
+    pub fullscreen: bool,
+    /// Index of the monitor to go fullscreen on, the current one if `None`.
+    pub monitor: Option<usize>,
+}
+
+impl Default for WindowConfig {
+    fn default() -> Self {
+        Self {
+            title: env!("CARGO_PKG_NAME").to_string(),
+            size: None,
+            position: None,
+            resizable: true,
+            icon: None,
+            fullscreen: false,
+            monitor: None,
+        }
+    }
+}
+
+impl WindowConfig {
+    /// Opens a window, e.g. a secondary one from within a [`Game`] callback.
+    pub fn open(&self, event_loop: &ActiveEventLoop) -> anyhow::Result<Arc<Window>> {
+        let window = event_loop
+            .create_window(self.attributes(event_loop))
+            .with_context(|| format!("Failed to create window {:?}", self.title))?;
+        Ok(Arc::new(window))
+    }
+
+    fn attributes(&self, event_loop: &ActiveEventLoop) -> WindowAttributes {
+        let monitors: Vec<_> = event_loop.available_monitors().collect();
+
+        let mut attributes = WindowAttributes::default()
+            .with_title(&self.title)
+            .with_resizable(self.resizable)
+            .with_window_icon(self.icon.clone());
+
+        if let Some(size) = self.size {
+            attributes = attributes.with_inner_size(size);
+        }
+
+        if let Some(position) = self.position {
+            let visible = monitors.iter().any(|monitor| {
+                let (origin, size) = (monitor.position(), monitor.size());
+                (origin.x..origin.x + size.width as i32).contains(&position.x)
+                    && (origin.y..origin.y + size.height as i32).contains(&position.y)
+            });
+
+            if visible {
+                attributes = attributes.with_position(position);
+            } else {
+                log::info!("Window position {position:?} is off screen, ignoring it.");
+            }
+        }
+
+        if self.fullscreen {
+            let monitor = self.monitor.and_then(|i| monitors.get(i).cloned());
+            attributes = attributes.with_fullscreen(Some(Fullscreen::Borderless(monitor)));
+        }
+
+        attributes
+    }
+}
+
+/// A game driven by [`GameWindow`].
+///
+/// The game renders to the main window created for it. It may open secondary
+/// windows with [`WindowConfig::open`], which it owns along with their
+/// surfaces. Events of every window are routed to it by [`WindowId`], redraws
+/// of secondary windows go to [`Self::render_window`] and closing one calls
+/// [`Self::window_closed`] instead of ending the game.
+pub trait Game: Sized {
+    /// Settings the game is started with, e.g. from the command line.
+    type Options;
+
+    /// Creates the game for its main window. Errors end the event loop.
+    fn init(window: Arc<Window>, options: &Self::Options) -> anyhow::Result<Self>;
+
+    /// Configuration of the main window.
+    fn window_config(_options: &Self::Options) -> WindowConfig {
+        WindowConfig::default()
+    }
+
+    /// Reports an error that keeps the game from running, the event loop exits
+    /// afterwards. Logs the message followed by its causes by default.
+    fn error(error: anyhow::Error) {
+        log::error!("{error:?}");
+    }
+
+    /// Advances the simulation by [`FIXED_TIMESTEP`], called as many times as
+    /// needed to keep up with real time before each frame.
+    fn update(&mut self, _dt: f32) {}
+
+    /// Draws a frame. `alpha` in `[0, 1)` is how far real time is past the last
+    /// update, in steps, for interpolating between the last two states.
+    fn render(&mut self, _event_loop: &winit::event_loop::ActiveEventLoop, _alpha: f32) {}
+
+    /// Draws a secondary window after it requested a redraw.
+    fn render_window(&mut self, _event_loop: &ActiveEventLoop, _window_id: WindowId) {}
+
+    /// A secondary window was asked to close, the game should drop it and its surface.
+    fn window_closed(&mut self, _window_id: WindowId) {}
+
+    /// The app was suspended, e.g. sent to the background on mobile, and can't
+    /// render until resumed. Surfaces should be dropped, everything else kept.
+    fn suspended(&mut self, _event_loop: &ActiveEventLoop) {}
+
+    /// The app was resumed after [`Self::suspended`], surfaces can be recreated.
+    /// Errors end the game.
+    fn resumed(&mut self, _event_loop: &ActiveEventLoop) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    /// While `true` neither `update` nor `render` are called and the event loop sleeps.
+    fn paused(&self) -> bool {
+        false
+    }
 
     fn window_event(
         &mut self,
         _event_loop: &winit::event_loop::ActiveEventLoop,
-        _window_id: winit::window::WindowId,
+        _window_id: WindowId,
         _event: WindowEvent,
     ) {
     }
@@ -26,37 +162,99 @@ pub trait Game {
     }
 
     fn about_to_wait(&mut self, _event_loop: &winit::event_loop::ActiveEventLoop) {}
+
+    fn exiting(&mut self, _event_loop: &winit::event_loop::ActiveEventLoop) {}
 }
 
+/// Runs a [`Game`] in a main window, driving its updates from a single clock.
 pub struct GameWindow<T: Game> {
+    options: T::Options,
     window: Option<Arc<Window>>,
     game: Option<T>,
+    /// Time of the last frame, `None` after a pause so the paused time is skipped.
+    last_frame: Option<Instant>,
+    /// Real time not yet simulated, less than a step after each frame.
+    accumulator: f32,
+    /// Set when the event loop exits because of an error.
+    failed: bool,
 }
 
-impl<T: Game> Default for GameWindow<T> {
-    fn default() -> Self {
+impl<T: Game> GameWindow<T> {
+    pub fn new(options: T::Options) -> Self {
         Self {
+            options,
             window: None,
             game: None,
+            last_frame: None,
+            accumulator: 0.0,
+            failed: false,
         }
     }
-}
 
-impl<T: Game> GameWindow<T> {
-    pub fn new() -> Self {
-        Self::default()
+    /// Whether the game stopped because of an error rather than being closed.
+    pub fn failed(&self) -> bool {
+        self.failed
+    }
+
+    fn fail(&mut self, event_loop: &ActiveEventLoop, error: anyhow::Error) {
+        T::error(error);
+        self.failed = true;
+        event_loop.exit();
+    }
+
+    /// Runs the updates due since the last frame, then renders.
+    fn frame(&mut self, event_loop: &winit::event_loop::ActiveEventLoop) {
+        let Some(game) = self.game.as_mut().filter(|game| !game.paused()) else {
+            return;
+        };
+
+        let now = Instant::now();
+        let frame_time = self
+            .last_frame
+            .map_or(0.0, |last| (now - last).as_secs_f32())
+            .min(MAX_FRAME_TIME);
+        self.last_frame = Some(now);
+
+        self.accumulator += frame_time;
+        while self.accumulator >= FIXED_TIMESTEP {
+            game.update(FIXED_TIMESTEP);
+            self.accumulator -= FIXED_TIMESTEP;
+        }
+
+        game.render(event_loop, self.accumulator / FIXED_TIMESTEP);
     }
 }
 
 impl<T: Game> ApplicationHandler for GameWindow<T> {
+    /// Creates the window and game on the first call, later ones follow a
+    /// suspension and only let the game recreate its surfaces.
     fn resumed(&mut self, event_loop: &winit::event_loop::ActiveEventLoop) {
-        self.window = Some(Arc::new(
-            event_loop
-                .create_window(WindowAttributes::default())
-                .unwrap(),
-        ));
+        if let Some(game) = self.game.as_mut() {
+            if let Err(e) = game.resumed(event_loop) {
+                self.fail(event_loop, e.context("Failed to resume"));
+            }
+            return;
+        }
+
+        let window = match T::window_config(&self.options).open(event_loop) {
+            Ok(window) => window,
+            Err(e) => return self.fail(event_loop, e),
+        };
 
-        self.game = Some(Game::init(self.window.clone().unwrap()));
+        match T::init(window.clone(), &self.options) {
+            Ok(game) => {
+                self.game = Some(game);
+                self.window = Some(window);
+            }
+            Err(e) => self.fail(event_loop, e.context("Failed to initialise the game")),
+        }
+    }
+
+    fn suspended(&mut self, event_loop: &winit::event_loop::ActiveEventLoop) {
+        if let Some(game) = self.game.as_mut() {
+            game.suspended(event_loop);
+        }
+        self.last_frame = None;
     }
 
     fn device_event(
@@ -65,25 +263,48 @@ impl<T: Game> ApplicationHandler for GameWindow<T> {
         device_id: winit::event::DeviceId,
         event: winit::event::DeviceEvent,
     ) {
-        self.game
-            .as_mut()
-            .unwrap()
-            .device_event(event_loop, device_id, event);
+        if let Some(game) = self.game.as_mut() {
+            game.device_event(event_loop, device_id, event);
+        }
     }
 
     fn about_to_wait(&mut self, event_loop: &winit::event_loop::ActiveEventLoop) {
-        self.game.as_mut().unwrap().about_to_wait(event_loop);
+        let (Some(game), Some(window)) = (self.game.as_mut(), &self.window) else {
+            return;
+        };
+        game.about_to_wait(event_loop);
+
+        if game.paused() {
+            event_loop.set_control_flow(ControlFlow::Wait);
+            self.last_frame = None;
+        } else {
+            event_loop.set_control_flow(ControlFlow::Poll);
+            window.request_redraw();
+        }
+    }
+
+    fn exiting(&mut self, event_loop: &winit::event_loop::ActiveEventLoop) {
+        if let Some(game) = self.game.as_mut() {
+            game.exiting(event_loop);
+        }
     }
 
     fn window_event(
         &mut self,
         event_loop: &winit::event_loop::ActiveEventLoop,
-        window_id: winit::window::WindowId,
+        window_id: WindowId,
         event: WindowEvent,
     ) {
-        self.game
-            .as_mut()
-            .unwrap()
-            .window_event(event_loop, window_id, event);
+        let main = self.window.as_ref().is_some_and(|w| w.id() == window_id);
+        let Some(game) = self.game.as_mut() else {
+            return;
+        };
+
+        match event {
+            WindowEvent::RedrawRequested if main => self.frame(event_loop),
+            WindowEvent::RedrawRequested => game.render_window(event_loop, window_id),
+            WindowEvent::CloseRequested if !main => game.window_closed(window_id),
+            event => game.window_event(event_loop, window_id, event),
+        }
     }
 }